@@ -1,13 +1,90 @@
+#[cfg(feature = "bindgen")]
 extern crate bindgen;
 
+#[cfg(feature = "bindgen")]
 use bindgen::EnumVariation;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Write;
+use std::process::Command;
+
+/// Find all directories below `base` which contain a build of the library.
+fn available_variants(base: &Path) -> Vec<String> {
+    fn scan(base: &Path, dir: &Path, result: &mut Vec<String>) {
+        if dir.join("libalgobsec.a").is_file() {
+            if let Ok(variant) = dir.strip_prefix(base) {
+                result.push(variant.display().to_string());
+            }
+        }
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                if entry.path().is_dir() {
+                    scan(base, &entry.path(), result);
+                }
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    scan(base, base, &mut result);
+    result.sort();
+    result
+}
+
+/// Read a path from an environment variable, and re-run the build if it changes.
+fn env_path(name: &str) -> Option<PathBuf> {
+    println!("cargo:rerun-if-env-changed={}", name);
+    env::var_os(name).map(PathBuf::from)
+}
+
+/// Check if we build for BSEC 2.x.
+fn is_bsec2() -> bool {
+    env::var_os("CARGO_FEATURE_BSEC2").is_some()
+}
+
+/// The directory of the Arduino library, relative to the project.
+fn library_dir() -> &'static str {
+    if is_bsec2() {
+        "Bosch-BSEC2-Library"
+    } else {
+        "BSEC-Arduino-library"
+    }
+}
+
+fn missing_library(what: &str, path: &Path) -> ! {
+    let checkout = if is_bsec2() {
+        "Clone https://github.com/boschsensortec/Bosch-BSEC2-Library into \"Bosch-BSEC2-Library\""
+    } else {
+        "Check out the \"BSEC-Arduino-library\" Git submodule: git submodule update --init"
+    };
+
+    panic!(
+        r#"Unable to find the BSEC library {} at: {}
+
+The BSEC library is not open source, so it cannot be shipped with this crate. You need to accept
+the license terms from Bosch, and provide the library yourself:
+
+* {}
+* Or point to a copy of the library using the environment variables:
+  * BSEC_LIB_DIR - the directory containing "libalgobsec.a"
+  * BSEC_INCLUDE_DIR - the directory containing "bsec_interface.h"
+  * BSEC_LIB_VARIANT - the variant to use, relative to "{}/src"
+  * BSEC_CONFIG_DIR - the directory containing the configurations
+"#,
+        what,
+        path.display(),
+        checkout,
+        library_dir()
+    );
+}
+
+fn select_lib_dir(base: &Path) -> PathBuf {
+    if let Some(variant) = env_path("BSEC_LIB_VARIANT") {
+        return base.join(variant);
+    }
 
-fn select_lib_dir(base: &PathBuf) -> PathBuf {
     let target = env::var_os("TARGET")
         .and_then(|os| os.into_string().ok())
         .unwrap_or_default();
@@ -16,45 +93,265 @@ fn select_lib_dir(base: &PathBuf) -> PathBuf {
         "" => {
             panic!("'TARGET' env-var is missing during build");
         }
+        "thumbv6m-none-eabi" => base.join("cortex-m0plus"),
+        "thumbv7m-none-eabi" => base.join("cortex-m3"),
+        "thumbv7em-none-eabi" => base.join("cortex-m4"),
+        // this is also the target for the Cortex-M7
         "thumbv7em-none-eabihf" => base.join("cortex-m4").join("fpv4-sp-d16-hard"),
+        "thumbv8m.main-none-eabi" => base.join("cortex-m33"),
+        "thumbv8m.main-none-eabihf" => base.join("cortex-m33").join("fpv5-sp-d16-hard"),
+        // Linux builds are not part of the Arduino library, but of the full BSEC distribution
+        "x86_64-unknown-linux-gnu" => base.join("x86_64"),
+        "aarch64-unknown-linux-gnu" => base.join("PiThree_ArmV8-a-64bits"),
+        "armv7-unknown-linux-gnueabihf" => base.join("PiThree_ArmV6"),
         other => {
-            panic!("Unsupported target platform ({}). Most likely the `build.rs` just misses a mapping. Pull requests are welcome ;-)", other);
+            panic!(
+                "Unsupported target platform ({}). Most likely the `build.rs` just misses a mapping. Pull requests are welcome ;-)\nAvailable variants of the library: {:?}",
+                other,
+                available_variants(base)
+            );
         }
     }
 }
 
-fn main() {
-    // examples
+/// The names of the configuration files, BSEC 1.x only has IAQ configurations, BSEC 2.x also has gas selectivity ones.
+const CONFIGURATION_FILES: &[&str] = &["bsec_iaq.txt", "bsec_selectivity.txt"];
 
-    if env::var_os("CARGO_FEATURE_STM32F4XX").is_some() {
-        // Put `memory.x` in our output directory and ensure it's
-        // on the linker search path.
-        let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
-        File::create(out.join("memory.x"))
+/// Find all directories, containing a configuration file.
+///
+/// For BSEC 2.x, the configurations are grouped by sensor (`config/bme688/bme688_sel_33v_3s_4d`).
+fn configuration_files(dir: &Path, result: &mut Vec<(PathBuf, PathBuf)>) {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    dirs.sort();
+
+    for dir in dirs.into_iter().filter(|dir| dir.is_dir()) {
+        match CONFIGURATION_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|file| file.is_file())
+        {
+            Some(file) => result.push((dir, file)),
+            None => configuration_files(&dir, result),
+        }
+    }
+}
+
+/// The directory of the configurations.
+///
+/// Unless overridden by `BSEC_CONFIG_DIR`, this is the `config` directory next to the headers,
+/// which is where the BSEC distribution keeps them.
+fn config_dir(base_search_dir: &Path) -> PathBuf {
+    env_path("BSEC_CONFIG_DIR").unwrap_or_else(|| {
+        env_path("BSEC_INCLUDE_DIR")
+            .and_then(|dir| dir.parent().map(|parent| parent.join("config")))
+            .unwrap_or_else(|| base_search_dir.join("config"))
+    })
+}
+
+fn generate_configurations(config_dir: &Path, out: &Path) {
+    if config_dir.is_dir() {
+        println!("cargo:rerun-if-changed={}", config_dir.display());
+    }
+
+    let mut files = Vec::new();
+    configuration_files(config_dir, &mut files);
+    if files.is_empty() {
+        println!(
+            "cargo:warning=No configurations found at {}, the `configuration` module is empty",
+            config_dir.display()
+        );
+    }
+
+    let mut content = String::new();
+
+    for (dir, file) in files {
+        println!("cargo:rerun-if-changed={}", file.display());
+
+        let name = dir.file_name().unwrap().to_str().unwrap().to_uppercase();
+        let data: Vec<u8> = fs::read_to_string(&file)
             .unwrap()
-            .write_all(include_bytes!("memory.x"))
-            .unwrap();
-        println!("cargo:rustc-link-search={}", out.display());
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse()
+                    .unwrap_or_else(|_| panic!("Invalid value '{}' in {}", s, file.display()))
+            })
+            .collect();
 
-        // By default, Cargo will re-run a build script whenever
-        // any file in the project changes. By specifying `memory.x`
-        // here, we ensure the build script is only re-run when
-        // `memory.x` is changed.
-        println!("cargo:rerun-if-changed=memory.x");
+        content.push_str(&format!(
+            "/// Configuration `{}/{}`\npub const {}: [u8; {}] = {:?};\n",
+            dir.file_name().unwrap().to_str().unwrap(),
+            file.file_stem().unwrap().to_str().unwrap(),
+            name,
+            data.len(),
+            data
+        ));
     }
 
-    // bindgen
+    File::create(out.join("configurations.rs"))
+        .unwrap()
+        .write_all(content.as_bytes())
+        .unwrap();
+}
 
-    let project_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
-    let base_search_dir = project_dir.join("BSEC-Arduino-library").join("src");
+/// Split Rust code into tokens: words (identifiers and numbers), string literals and single
+/// punctuation characters. Whitespace and comments are skipped.
+fn tokenize(code: &str) -> Vec<&str> {
+    fn string_len(code: &str) -> usize {
+        let mut escaped = false;
+        for (i, c) in code.char_indices().skip(1) {
+            match c {
+                '"' if !escaped => return i + 1,
+                '\\' if !escaped => escaped = true,
+                _ => escaped = false,
+            }
+        }
+        code.len()
+    }
 
-    let search_dir = select_lib_dir(&base_search_dir);
+    let mut tokens = Vec::new();
+    let mut rest = code.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let (len, comment) = if rest.starts_with("//") {
+            (rest.find('\n').unwrap_or(rest.len()), true)
+        } else if rest.starts_with("/*") {
+            (rest.find("*/").map_or(rest.len(), |end| end + 2), true)
+        } else if c == '"' {
+            (string_len(rest), false)
+        } else {
+            let word = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (word.max(c.len_utf8()), false)
+        };
 
-    println!(
-        "cargo:rustc-link-search={}",
-        search_dir.as_path().to_str().unwrap()
-    );
-    println!("cargo:rustc-link-lib=static=algobsec");
+        let (token, tail) = rest.split_at(len);
+        if !comment {
+            tokens.push(token);
+        }
+        rest = tail.trim_start();
+    }
+    tokens
+}
+
+/// Parse the variants of a (Rust) enum from the bindings.
+///
+/// The bindings are tokenized, so this works whether they were formatted or not.
+fn parse_enum(bindings: &str, name: &str) -> Vec<(String, i64)> {
+    let tokens = tokenize(bindings);
+    let start = match tokens
+        .windows(3)
+        .position(|window| window == ["enum", name, "{"])
+    {
+        Some(start) => start + 3,
+        None => return Vec::new(),
+    };
+
+    let mut variants = Vec::new();
+    let mut tokens = tokens[start..].iter().copied();
+    loop {
+        match tokens.next() {
+            None | Some("}") => break,
+            Some(",") => {}
+            // an attribute, like the doc comment of the variant
+            Some("#") => {
+                let mut depth = 0;
+                for token in tokens.by_ref() {
+                    match token {
+                        "[" => depth += 1,
+                        "]" if depth == 1 => break,
+                        "]" => depth -= 1,
+                        _ => {}
+                    }
+                }
+            }
+            Some(variant) => {
+                let value = match (tokens.next(), tokens.next()) {
+                    (Some("="), Some("-")) => tokens
+                        .next()
+                        .and_then(|value| value.parse::<i64>().ok())
+                        .map(|value| -value),
+                    (Some("="), Some(value)) => value.parse().ok(),
+                    _ => None,
+                };
+                let value = value.unwrap_or_else(|| {
+                    panic!("Unable to parse the value of {}::{}", name, variant)
+                });
+                variants.push((variant.to_string(), value));
+            }
+        }
+    }
+    variants
+}
+
+/// Generate the tables of the virtual and physical sensors, and the conversions of the return
+/// codes, based on the enums of the bindings.
+fn generate_sensors(bindings: &Path, out: &Path) {
+    println!("cargo:rerun-if-changed={}", bindings.display());
+
+    let bindings = fs::read_to_string(bindings).unwrap();
+    let virtual_sensors = parse_enum(&bindings, "bsec_virtual_sensor_t");
+    let physical_sensors = parse_enum(&bindings, "bsec_physical_sensor_t");
+    let return_codes = parse_enum(&bindings, "bsec_library_return_t");
+    if virtual_sensors.is_empty() || physical_sensors.is_empty() || return_codes.is_empty() {
+        panic!("Unable to find the enums in the bindings");
+    }
+
+    let mut content = String::new();
+
+    content.push_str(&format!(
+        "/// Number of virtual sensors.\npub(crate) const NUM_VIRTUAL_SENSORS: u8 = {};\n\n",
+        virtual_sensors.len()
+    ));
+
+    content.push_str(&format!(
+        "/// Number of physical sensors.\npub(crate) const NUM_PHYSICAL_SENSORS: u8 = {};\n\n",
+        physical_sensors.len()
+    ));
+
+    content.push_str("impl bsec_virtual_sensor_t {\n");
+    content.push_str("    /// All known sensors.\n");
+    content.push_str("    pub const ALL: &'static [bsec_virtual_sensor_t] = &[\n");
+    for (variant, _) in &virtual_sensors {
+        content.push_str(&format!("        bsec_virtual_sensor_t::{},\n", variant));
+    }
+    content.push_str("    ];\n}\n\n");
+
+    for (name, repr, variants) in &[
+        ("bsec_virtual_sensor_t", "u8", &virtual_sensors),
+        ("bsec_physical_sensor_t", "u8", &physical_sensors),
+        ("bsec_library_return_t", "i32", &return_codes),
+    ] {
+        content.push_str(&format!("impl TryFrom<{}> for {} {{\n", repr, name));
+        content.push_str("    type Error = ();\n\n");
+        content.push_str(&format!(
+            "    fn try_from(value: {}) -> Result<Self, Self::Error> {{\n",
+            repr
+        ));
+        content.push_str("        match value {\n");
+        for (variant, value) in variants.iter() {
+            content.push_str(&format!("            {} => Ok({}::{}),\n", value, name, variant));
+        }
+        content.push_str("            _ => Err(()),\n");
+        content.push_str("        }\n    }\n}\n\n");
+    }
+
+    File::create(out.join("sensors.rs"))
+        .unwrap()
+        .write_all(content.as_bytes())
+        .unwrap();
+}
+
+/// Generate the bindings from the headers, instead of using the pre-generated ones.
+#[cfg(feature = "bindgen")]
+fn generate_bindings(base_search_dir: &Path) -> PathBuf {
+    let include_dir = env_path("BSEC_INCLUDE_DIR").unwrap_or_else(|| base_search_dir.join("inc"));
+    if !include_dir.join("bsec_interface.h").is_file() {
+        missing_library("headers", &include_dir);
+    }
 
     // Tell cargo to invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed=src/wrapper.h");
@@ -66,7 +363,7 @@ fn main() {
         // The input header we would like to generate
         // bindings for.
         .header("src/wrapper.h")
-        .clang_arg("-I./BSEC-Arduino-library/src")
+        .clang_arg(format!("-I{}", include_dir.display()))
         // Tell cargo to invalidate the built crate whenever any of the
         // included header files changed.
         .parse_callbacks(Box::new(bindgen::CargoCallbacks))
@@ -77,7 +374,9 @@ fn main() {
         // whitelist only bsec types
         .whitelist_function("bsec_.*")
         .whitelist_type("bsec_.*")
-        .whitelist_var("bsesc_.*")
+        .whitelist_var("BSEC_.*")
+        // allow creating empty structs
+        .derive_default(true)
         .default_enum_style(EnumVariation::Rust {
             non_exhaustive: false,
         })
@@ -91,4 +390,145 @@ fn main() {
     bindings
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
+
+    out_path.join("bindings.rs")
+}
+
+// the C math functions, which might be referenced by the library, shared with the tests
+include!("src/math_symbols.rs");
+
+/// Check if we need to provide the C math functions ourselves.
+///
+/// This is only the case for bare metal targets, everything else has a C library, and exporting the symbols would
+/// collide with it.
+fn use_libm_shims() -> bool {
+    env::var_os("CARGO_FEATURE_LIBM_SHIMS").is_some()
+        && env::var_os("CARGO_FEATURE_STD").is_none()
+        && env::var("CARGO_CFG_TARGET_OS").map_or(true, |os| os == "none")
+}
+
+/// Collect the names of the shims, provided by `src/compat.rs`.
+fn provided_shims() -> Vec<String> {
+    println!("cargo:rerun-if-changed=src/compat.rs");
+    fs::read_to_string("src/compat.rs")
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("pub extern \"C\" fn "))
+        .filter_map(|line| line.split('(').next())
+        .map(String::from)
+        .collect()
+}
+
+/// Check the undefined symbols of the library, and warn about math functions which have no shim.
+///
+/// This requires `nm` (or whatever `NM` points to), if it isn't available, the check is skipped.
+fn check_shims(archive: &Path) {
+    println!("cargo:rerun-if-env-changed=NM");
+    let nm = env::var("NM").unwrap_or_else(|_| "nm".into());
+
+    let output = match Command::new(&nm).arg("-u").arg(archive).output() {
+        Ok(output) if output.status.success() => output,
+        _ => {
+            println!(
+                "cargo:warning=Unable to run '{}', skipping check of the libm shims",
+                nm
+            );
+            return;
+        }
+    };
+
+    let shims = provided_shims();
+    let mut missing: Vec<_> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .filter(|symbol| MATH_SYMBOLS.contains(symbol))
+        .filter(|symbol| !shims.iter().any(|shim| shim == symbol))
+        .map(String::from)
+        .collect();
+    missing.sort();
+    missing.dedup();
+
+    for symbol in missing {
+        println!(
+            "cargo:warning=The BSEC library references '{}', which is not provided by the libm shims (src/compat.rs)",
+            symbol
+        );
+    }
+}
+
+fn main() {
+    // set below, when the library is linked on a bare metal target
+    println!("cargo:rustc-check-cfg=cfg(libm_shims)");
+
+    // examples
+
+    if env::var_os("CARGO_FEATURE_STM32F4XX").is_some() {
+        // Put `memory.x` in our output directory and ensure it's
+        // on the linker search path.
+        let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
+        File::create(out.join("memory.x"))
+            .unwrap()
+            .write_all(include_bytes!("memory.x"))
+            .unwrap();
+        println!("cargo:rustc-link-search={}", out.display());
+
+        // By default, Cargo will re-run a build script whenever
+        // any file in the project changes. By specifying `memory.x`
+        // here, we ensure the build script is only re-run when
+        // `memory.x` is changed.
+        println!("cargo:rerun-if-changed=memory.x");
+    }
+
+    // the variant is only read when selecting the library, but changing it must always re-run
+    println!("cargo:rerun-if-env-changed=BSEC_LIB_VARIANT");
+
+    let project_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let base_search_dir = project_dir.join(library_dir()).join("src");
+
+    // configurations
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    generate_configurations(&config_dir(&base_search_dir), &out_dir);
+
+    if env::var_os("CARGO_FEATURE_MOCK").is_some() {
+        // the mock backend doesn't link the library, but shares the types of the BSEC 1.x bindings
+        generate_sensors(Path::new("src/bindings/bsec_1_4_8_0.rs"), &out_dir);
+        return;
+    }
+
+    // library
+
+    let search_dir = env_path("BSEC_LIB_DIR").unwrap_or_else(|| select_lib_dir(&base_search_dir));
+    if !search_dir.join("libalgobsec.a").is_file() {
+        missing_library("archive", &search_dir);
+    }
+
+    println!(
+        "cargo:rustc-link-search={}",
+        search_dir.as_path().to_str().unwrap()
+    );
+    println!("cargo:rustc-link-lib=static=algobsec");
+
+    // libm shims
+
+    if use_libm_shims() {
+        println!("cargo:rustc-cfg=libm_shims");
+        check_shims(&search_dir.join("libalgobsec.a"));
+    }
+
+    // bindgen
+
+    #[cfg(feature = "bindgen")]
+    let bindings = generate_bindings(&base_search_dir);
+    #[cfg(not(feature = "bindgen"))]
+    let bindings = PathBuf::from(if is_bsec2() {
+        "src/bindings/bsec_2_0_6_1.rs"
+    } else {
+        "src/bindings/bsec_1_4_8_0.rs"
+    });
+
+    // sensors
+
+    generate_sensors(&bindings, &out_dir);
 }
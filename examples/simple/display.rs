@@ -1,5 +1,4 @@
-use drogue_bme680::Data;
-use drogue_bsec::{Accuracy, Outputs};
+use drogue_bsec::{Accuracy, Measurement, Outputs};
 use embedded_graphics::drawable::Drawable;
 use embedded_graphics::fonts::{Font, Font24x32, Font6x8, Text};
 use embedded_graphics::geometry::Point;
@@ -172,7 +171,7 @@ where
         self.draw_text(&b2, font, color, vpadding, top, left, width)
     }
 
-    pub fn set_state(&mut self, data: &Data, outputs: &Outputs) -> Result<(), ()> {
+    pub fn set_state(&mut self, data: &Measurement, outputs: &Outputs) -> Result<(), ()> {
         let size = self.inner.size();
 
         let mut top = 0;
@@ -65,15 +65,12 @@ use drogue_bme680::{
 use log::LevelFilter;
 use rtt_logger::RTTLogger;
 
-use drogue_bsec::{Bsec, Inputs, SampleRate};
+use drogue_bsec::{Bme680ControllerSensor, Bsec, BsecDriver, SampleRate};
 use drogue_embedded_timer::{MillisecondsClock1, MillisecondsTicker1};
-use embedded_time::duration::Milliseconds;
 
 use core::cell::RefCell;
-use core::convert::TryInto;
 use core::ops::DerefMut;
 use cortex_m::interrupt::{free, Mutex};
-use embedded_time::Clock;
 use hal::timer::Timer;
 use stm32f4xx_hal::timer::Event;
 
@@ -100,15 +97,6 @@ fn TIM2() {
     });
 }
 
-fn get_now() -> Milliseconds {
-    CLOCK
-        .try_now()
-        .unwrap()
-        .duration_since_epoch()
-        .try_into()
-        .unwrap()
-}
-
 #[entry]
 fn main() -> ! {
     rtt_init_print!(NoBlockSkip, 4096);
@@ -216,7 +204,7 @@ fn main() -> ! {
 
     let bme680 = Bme680Sensor::from(i2c, Address::Secondary).unwrap();
 
-    let mut controller =
+    let controller =
         Bme680Controller::new(bme680, delay, Configuration::standard(), StaticProvider(25))
             .unwrap();
 
@@ -228,71 +216,24 @@ fn main() -> ! {
     )
     .unwrap();
 
-    let mut next = Milliseconds(0u32);
+    let mut driver = BsecDriver::new(bsec, Bme680ControllerSensor::new(controller), &CLOCK);
+
     loop {
-        let now = get_now();
-
-        if now > next {
-            log::info!("start timestamp: {}", now);
-
-            let control = bsec.sensor_control(now).unwrap();
-            next = control.next_call;
-
-            // process "control"
-            let config = Configuration {
-                filter: Filter::Coefficient3,
-                temperature: control.temperature_oversampling,
-                pressure: control.pressure_oversampling,
-                humidity: control.humidity_oversampling,
-                heater_temperature: control.heater_temperature,
-                heater_duration: control.heating_duration,
-                run_gas: control.run_gas,
-            };
-
-            controller.update_configuration(config).unwrap();
-
-            if control.trigger_measurement {
-                let data = controller.measure_default().unwrap().unwrap();
-
-                let gas_resistance = if control.run_gas {
-                    Some(data.gas_resistance)
-                } else {
-                    None
-                };
-
-                let inputs = Inputs {
-                    temperature: Some(data.temperature),
-                    humidity: Some(data.humidity),
-                    pressure: data.pressure,
-                    gas_resistance,
-                };
-
-                log::info!("Processing inputs: {:?}", inputs);
-
-                let outputs = bsec.process_data(get_now(), &inputs).unwrap();
-
-                #[cfg(feature = "display")]
-                if let Some(display) = &mut display {
-                    display.set_state(&data, &outputs).unwrap();
-                }
-                #[cfg(not(feature = "display"))]
-                {
-                    log::info!("Output: {:?}", outputs);
-                }
-
-                log::info!("end timestamp - {}", get_now());
+        let poll = driver.poll().unwrap();
+
+        if let (Some(_data), Some(outputs)) = (poll.data, poll.outputs) {
+            #[cfg(feature = "display")]
+            if let Some(display) = &mut display {
+                display.set_state(&_data, &outputs).unwrap();
+            }
+            #[cfg(not(feature = "display"))]
+            {
+                log::info!("Output: {:?}", outputs);
             }
         }
 
         // sleep
 
-        let now = get_now();
-        let diff = next - now;
-        if next > now {
-            if diff > Milliseconds(0u32) {
-                log::info!("Sleeping for: {} ms", diff);
-                controller.delay(diff);
-            }
-        }
+        driver.sleep().unwrap();
     }
 }
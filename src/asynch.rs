@@ -0,0 +1,353 @@
+//! An async driver, running the BSEC loop for an environmental sensor, and an async BME680.
+
+use crate::bme680::*;
+use crate::driver::{driver_accessors, DriverCore};
+use crate::{
+    AsyncEnvironmentalSensor, Bsec, Control, DriverError, Measurement, Outputs, Timestamp,
+};
+use core::convert::{Infallible, TryFrom};
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+use embedded_time::duration::{Generic, Nanoseconds};
+use embedded_time::Clock;
+
+/// The number of times the sensor gets polled for new data, after the measurement should be done.
+const MAX_DATA_POLLS: usize = 10;
+/// The time between polling for new data, in milliseconds.
+const DATA_POLL_INTERVAL: u32 = 5;
+
+/// An error of the [`AsyncBme680`], with the error type `E` of the I2C bus.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AsyncBme680Error<E> {
+    /// Communicating over the I2C bus failed.
+    I2c(E),
+    /// The chip ID isn't the one of a BME680.
+    ChipId(u8),
+}
+
+/// A BME680, accessed using async I2C.
+///
+/// This only supports what BSEC needs: forced mode measurements, using a single heater step.
+///
+/// The delay waits for the measurements. It is also used for the [`DelayNs`] implementation, so
+/// that [`AsyncBsecDriver::sleep`] can use it.
+pub struct AsyncBme680<I2C, D> {
+    i2c: I2C,
+    delay: D,
+    address: u8,
+    calibration: Calibration,
+    ambient_temperature: f32,
+    /// The settings applied by the last call to `configure`.
+    settings: Settings,
+}
+
+/// The settings of a measurement, requested by BSEC.
+#[derive(Copy, Clone, Debug, Default)]
+struct Settings {
+    osrs_t: u8,
+    osrs_p: u8,
+    osrs_h: u8,
+    /// The heating duration, in milliseconds, if the gas is measured.
+    heating_duration: Option<u16>,
+}
+
+impl<I2C, D> AsyncBme680<I2C, D>
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    /// Reset the sensor, and read its calibration.
+    pub async fn new(
+        i2c: I2C,
+        address: Address,
+        delay: D,
+    ) -> Result<Self, AsyncBme680Error<I2C::Error>> {
+        let mut sensor = AsyncBme680 {
+            i2c,
+            delay,
+            address: address as u8,
+            calibration: Calibration::default(),
+            ambient_temperature: 25.0,
+            settings: Settings::default(),
+        };
+
+        sensor.write_register(REG_RESET, SOFT_RESET).await?;
+        sensor.delay.delay_ms(10).await;
+
+        let id = sensor.read_register(REG_ID).await?;
+        if id != CHIP_ID {
+            return Err(AsyncBme680Error::ChipId(id));
+        }
+
+        let mut coeff_1 = [0u8; COEFF_1_LEN];
+        let mut coeff_2 = [0u8; COEFF_2_LEN];
+        sensor.read_registers(REG_COEFF_1, &mut coeff_1).await?;
+        sensor.read_registers(REG_COEFF_2, &mut coeff_2).await?;
+        let res_heat_range = sensor.read_register(REG_RES_HEAT_RANGE).await?;
+        let res_heat_val = sensor.read_register(REG_RES_HEAT_VAL).await?;
+        let range_sw_err = sensor.read_register(REG_RANGE_SW_ERR).await?;
+
+        sensor.calibration = Calibration::parse(
+            &coeff_1,
+            &coeff_2,
+            res_heat_range,
+            res_heat_val,
+            range_sw_err,
+        );
+
+        Ok(sensor)
+    }
+
+    /// Release the bus and the delay.
+    pub fn release(self) -> (I2C, D) {
+        (self.i2c, self.delay)
+    }
+
+    fn compensate(&self, field: &FieldData) -> Measurement {
+        let t_fine = self.calibration.t_fine(field.temperature);
+
+        Measurement {
+            temperature: Calibration::temperature(t_fine),
+            humidity: self.calibration.humidity(t_fine, field.humidity),
+            pressure: if self.settings.osrs_p != 0 {
+                Some(self.calibration.pressure(t_fine, field.pressure))
+            } else {
+                None
+            },
+            gas_resistance: if self.settings.heating_duration.is_some()
+                && field.gas_valid
+                && field.heat_stable
+            {
+                Some(self.calibration.gas_resistance(field.gas, field.gas_range))
+            } else {
+                None
+            },
+        }
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, AsyncBme680Error<I2C::Error>> {
+        let mut buf = [0u8];
+        self.read_registers(register, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn read_registers(
+        &mut self,
+        register: u8,
+        buf: &mut [u8],
+    ) -> Result<(), AsyncBme680Error<I2C::Error>> {
+        self.i2c
+            .write_read(self.address, &[register], buf)
+            .await
+            .map_err(AsyncBme680Error::I2c)
+    }
+
+    async fn write_register(
+        &mut self,
+        register: u8,
+        value: u8,
+    ) -> Result<(), AsyncBme680Error<I2C::Error>> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .await
+            .map_err(AsyncBme680Error::I2c)
+    }
+}
+
+impl<I2C, D> AsyncEnvironmentalSensor for AsyncBme680<I2C, D>
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    type Error = AsyncBme680Error<I2C::Error>;
+
+    async fn configure(&mut self, control: &Control) -> Result<(), Self::Error> {
+        let settings = Settings {
+            osrs_t: control.temperature_oversampling.into(),
+            osrs_p: control.pressure_oversampling.into(),
+            osrs_h: control.humidity_oversampling.into(),
+            heating_duration: if control.run_gas {
+                Some(control.heating_duration.0.min(u16::MAX as u32) as u16)
+            } else {
+                None
+            },
+        };
+
+        self.write_register(REG_CTRL_HUM, settings.osrs_h).await?;
+        self.write_register(REG_CONFIG, FILTER_3 << 2).await?;
+
+        if let Some(heating_duration) = settings.heating_duration {
+            let res_heat = self
+                .calibration
+                .res_heat(control.heater_temperature, self.ambient_temperature);
+            self.write_register(REG_RES_HEAT_0, res_heat).await?;
+            self.write_register(REG_GAS_WAIT_0, gas_wait(heating_duration))
+                .await?;
+            self.write_register(REG_CTRL_GAS_0, 0).await?;
+            self.write_register(REG_CTRL_GAS_1, RUN_GAS).await?;
+        } else {
+            self.write_register(REG_CTRL_GAS_0, HEAT_OFF).await?;
+            self.write_register(REG_CTRL_GAS_1, 0).await?;
+        }
+
+        self.settings = settings;
+        Ok(())
+    }
+
+    async fn measure(&mut self) -> Result<Option<Measurement>, Self::Error> {
+        let Settings {
+            osrs_t,
+            osrs_p,
+            osrs_h,
+            heating_duration,
+        } = self.settings;
+
+        let mut duration = measurement_duration(osrs_t, osrs_p, osrs_h);
+        duration += heating_duration.unwrap_or_default() as u32;
+
+        self.write_register(REG_CTRL_MEAS, (osrs_t << 5) | (osrs_p << 2) | MODE_FORCED)
+            .await?;
+        self.delay.delay_ms(duration).await;
+
+        for _ in 0..MAX_DATA_POLLS {
+            let mut buf = [0u8; FIELD_LEN];
+            self.read_registers(REG_FIELD_0, &mut buf).await?;
+            let field = FieldData::parse(&buf);
+
+            if field.has_new_data() {
+                let measurement = self.compensate(&field);
+                self.ambient_temperature = measurement.temperature;
+                return Ok(Some(measurement));
+            }
+
+            self.delay.delay_ms(DATA_POLL_INTERVAL).await;
+        }
+
+        Ok(None)
+    }
+}
+
+impl<I2C, D> DelayNs for AsyncBme680<I2C, D>
+where
+    I2C: I2c,
+    D: DelayNs,
+{
+    async fn delay_ns(&mut self, ns: u32) {
+        self.delay.delay_ns(ns).await
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        self.delay.delay_ms(ms).await
+    }
+}
+
+/// Runs the BSEC loop for an [`AsyncEnvironmentalSensor`], without blocking: waiting for the
+/// next call and for the measurement is done using an async delay.
+///
+/// ```ignore
+/// let sensor = AsyncBme680::new(i2c, Address::Secondary, delay).await?;
+/// let mut driver = AsyncBsecDriver::new(bsec, sensor, &CLOCK);
+/// loop {
+///     let outputs = driver.next_outputs().await?;
+///     log::info!("IAQ: {:?}", outputs.iaq);
+/// }
+/// ```
+pub struct AsyncBsecDriver<'c, S, C>
+where
+    S: AsyncEnvironmentalSensor,
+    C: Clock,
+{
+    core: DriverCore<'c, S, C>,
+    last_measurement: Option<Measurement>,
+}
+
+impl<'c, S, C> AsyncBsecDriver<'c, S, C>
+where
+    S: AsyncEnvironmentalSensor,
+    C: Clock,
+    Nanoseconds<u64>: TryFrom<Generic<C::T>>,
+{
+    /// Create a new driver.
+    ///
+    /// The subscription of the `bsec` instance must already be set up.
+    pub fn new(bsec: Bsec, sensor: S, clock: &'c C) -> Self {
+        AsyncBsecDriver {
+            core: DriverCore::new(bsec, sensor, clock),
+            last_measurement: None,
+        }
+    }
+
+    driver_accessors!();
+
+    /// The measurement which was processed last.
+    pub fn last_measurement(&self) -> Option<&Measurement> {
+        self.last_measurement.as_ref()
+    }
+
+    /// Run a single iteration of the loop, if it is due.
+    ///
+    /// If BSEC requests a measurement, the sensor is configured, the measurement is performed,
+    /// and the outputs of processing it are returned.
+    pub async fn poll(&mut self) -> Result<Option<Outputs>, DriverError<S::Error>> {
+        let (control, now) = match self.core.control()? {
+            Some(control) => control,
+            None => return Ok(None),
+        };
+
+        let sensor = &mut self.core.sensor;
+        sensor
+            .configure(&control)
+            .await
+            .map_err(DriverError::Sensor)?;
+        let measurement = match sensor.measure().await.map_err(DriverError::Sensor)? {
+            Some(measurement) => measurement,
+            None => {
+                log::warn!("Measurement didn't provide any data");
+                return Ok(None);
+            }
+        };
+
+        let outputs = self.core.process(&control, now, &measurement)?;
+        self.last_measurement = Some(measurement);
+
+        Ok(Some(outputs))
+    }
+}
+
+impl<'c, S, C> AsyncBsecDriver<'c, S, C>
+where
+    S: AsyncEnvironmentalSensor + DelayNs,
+    C: Clock,
+    Nanoseconds<u64>: TryFrom<Generic<C::T>>,
+{
+    /// Wait until the next call is due, using the delay of the sensor.
+    pub async fn sleep(&mut self) -> Result<(), DriverError<S::Error>> {
+        if let Some(diff) = self.core.time_to_next_call()? {
+            self.core.sensor.delay_ms(diff).await;
+        }
+        Ok(())
+    }
+
+    /// Wait for the next outputs of BSEC, running the loop until a measurement got processed.
+    pub async fn next_outputs(&mut self) -> Result<Outputs, DriverError<S::Error>> {
+        loop {
+            self.sleep().await?;
+            if let Some(outputs) = self.poll().await? {
+                return Ok(outputs);
+            }
+        }
+    }
+
+    /// Run the loop, passing all outputs to the `sink`.
+    ///
+    /// This only returns when an error occurs.
+    pub async fn run<F>(&mut self, mut sink: F) -> Result<Infallible, DriverError<S::Error>>
+    where
+        F: FnMut(&Outputs),
+    {
+        loop {
+            let outputs = self.next_outputs().await?;
+            sink(&outputs);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Replay a recorded trace, or CSV measurements, through the BSEC library.
+//!
+//! Run `bsec-replay --help` for the usage.
+
+use drogue_bsec::replay::{
+    read_csv, Comparator, Format, OutputWriter, Recording, ReplayError, Replayer, Step,
+};
+use drogue_bsec::trace::{Trace, TRACE_MAGIC};
+use drogue_bsec::{
+    bsec_virtual_sensor_t, Bsec, Inputs, Outputs, SampleRate, Subscription, Timestamp,
+};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::process::exit;
+
+const USAGE: &str = "Usage: bsec-replay [OPTIONS] <INPUT>
+
+Replays a trace (recorded with the `dump` feature) or a CSV file through the BSEC library,
+and writes the outputs. A trace is replayed call by call, with the recorded timestamps and
+inputs. CSV measurements are provided when BSEC requests them, in simulated time.
+
+The CSV columns are: timestamp (ms), temperature (°C), humidity (%), pressure (Pa) and gas
+resistance (Ohm). Pressure and gas resistance may be empty.
+
+Options:
+  -o, --output <FILE>         Write the outputs to a file, instead of stdout
+  -f, --format <FORMAT>       The format of the outputs: csv (default) or json
+      --config <FILE>         Apply a serialized configuration
+      --state <FILE>          Apply a serialized state
+      --sample-rate <RATE>    The sample rate of all outputs: lp (default), ulp or on-demand.
+                              Overrides the subscription recorded in a trace
+      --speed <FACTOR>        Pace the replay in real time, accelerated by FACTOR
+      --compare               Compare the outputs to the ones recorded in the trace
+      --tolerance <VALUE>     The allowed difference, when comparing (default: 0.01)
+  -h, --help                  Print this help";
+
+struct Args {
+    input: String,
+    output: Option<String>,
+    format: Format,
+    config: Option<String>,
+    state: Option<String>,
+    sample_rate: Option<SampleRate>,
+    speed: Option<f64>,
+    compare: bool,
+    tolerance: f32,
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = Args {
+            input: String::new(),
+            output: None,
+            format: Format::Csv,
+            config: None,
+            state: None,
+            sample_rate: None,
+            speed: None,
+            compare: false,
+            tolerance: 0.01,
+        };
+
+        let mut input = None;
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            let mut value = || {
+                iter.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    exit(0);
+                }
+                "-o" | "--output" => args.output = Some(value()?),
+                "-f" | "--format" => {
+                    args.format = match value()?.as_str() {
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        format => return Err(format!("Unknown format: {}", format)),
+                    }
+                }
+                "--config" => args.config = Some(value()?),
+                "--state" => args.state = Some(value()?),
+                "--sample-rate" => {
+                    args.sample_rate = Some(match value()?.as_str() {
+                        "lp" => SampleRate::LowPower,
+                        "ulp" => SampleRate::UltraLowPower,
+                        "on-demand" => SampleRate::OnDemand,
+                        rate => return Err(format!("Unknown sample rate: {}", rate)),
+                    })
+                }
+                "--speed" => {
+                    args.speed = Some(
+                        value()?
+                            .parse()
+                            .map_err(|_| "Invalid speed factor".to_string())?,
+                    )
+                }
+                "--compare" => args.compare = true,
+                "--tolerance" => {
+                    args.tolerance = value()?
+                        .parse()
+                        .map_err(|_| "Invalid tolerance".to_string())?
+                }
+                _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+                _ if input.is_none() => input = Some(arg),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+
+        args.input = input.ok_or_else(|| "Missing input".to_string())?;
+        Ok(args)
+    }
+}
+
+fn run(args: Args) -> Result<bool, Box<dyn Error>> {
+    let data = std::fs::read(&args.input)?;
+    let (mut recording, samples) = if data.starts_with(&TRACE_MAGIC) {
+        let recording = Recording::from_trace(&Trace::parse(&data)?)?;
+        if !recording.fresh_instance {
+            eprintln!(
+                "Warning: the trace doesn't start from a fresh instance. The configuration, state \
+                 and subscription before the trace are unknown, so the replay may diverge."
+            );
+        }
+        (recording, Vec::new())
+    } else {
+        let samples = read_csv(BufReader::new(data.as_slice()))?;
+        (Recording::default(), samples)
+    };
+
+    if args.compare && recording.outputs.is_empty() {
+        return Err("There are no recorded outputs to compare with".into());
+    }
+
+    #[cfg(not(feature = "bsec2"))]
+    let bsec = Bsec::new()?;
+    // the only instance, kept until the process exits
+    #[cfg(feature = "bsec2")]
+    let bsec = Bsec::new(Box::leak(Box::default()))?;
+    let mut bsec = bsec.ok_or("The BSEC library is already in use")?;
+    if let Some(config) = &args.config {
+        bsec.set_configuration(&std::fs::read(config)?)?;
+    }
+    if let Some(state) = &args.state {
+        bsec.set_state(&std::fs::read(state)?)?;
+    }
+
+    // a trace applies its subscriptions, unless overridden
+    let is_subscription = |step: &Step| matches!(step, Step::UpdateSubscription(_));
+    if args.sample_rate.is_some() {
+        recording.steps.retain(|step| !is_subscription(step));
+    }
+    if !recording.steps.iter().any(is_subscription) {
+        let rate = args.sample_rate.unwrap_or(SampleRate::LowPower);
+        let subscription = bsec_virtual_sensor_t::ALL
+            .iter()
+            .fold(Subscription::new(), |s, sensor| s.with(*sensor, rate));
+        bsec.subscribe(&subscription)?;
+    }
+
+    let output: Box<dyn Write> = match &args.output {
+        Some(output) => Box::new(BufWriter::new(File::create(output)?)),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut writer = OutputWriter::new(output, args.format);
+    let mut comparator = Comparator::new(&recording.outputs, args.tolerance);
+
+    let mut replayer = Replayer::new(&mut bsec);
+    if let Some(speed) = args.speed {
+        replayer = replayer.with_acceleration(speed);
+    }
+    let mut sink = |timestamp: Timestamp, _: &Inputs, outputs: &Outputs| {
+        writer.write(timestamp, outputs)?;
+        comparator.compare(timestamp, outputs);
+        Ok::<_, ReplayError>(())
+    };
+    if samples.is_empty() {
+        let replayed = replayer.replay(&recording.steps, &mut sink)?;
+        eprintln!(
+            "Replayed {} calls, {} measurements processed",
+            recording.steps.len(),
+            replayed.processed
+        );
+        if replayed.diverged > 0 {
+            eprintln!(
+                "The sensor settings of {} calls differ from the recording",
+                replayed.diverged
+            );
+        }
+    } else {
+        let processed = replayer.run(&samples, &mut sink)?;
+        eprintln!(
+            "Replayed {} samples, {} measurements processed",
+            samples.len(),
+            processed
+        );
+    }
+    writer.finish()?;
+
+    if args.compare {
+        let comparison = comparator.finish();
+        eprint!("{}", comparison);
+        return Ok(comparison.is_match());
+    }
+
+    Ok(true)
+}
+
+fn main() {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            exit(2);
+        }
+    };
+
+    match run(args) {
+        Ok(true) => {}
+        Ok(false) => exit(1),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            exit(2);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+/* automatically generated by rust-bindgen 0.55.1 */
+
+pub const BSEC_MAX_WORKBUFFER_SIZE: u32 = 4096;
+pub const BSEC_MAX_PHYSICAL_SENSOR: u32 = 8;
+pub const BSEC_MAX_PROPERTY_BLOB_SIZE: u32 = 2277;
+pub const BSEC_MAX_STATE_BLOB_SIZE: u32 = 221;
+pub const BSEC_NUMBER_OUTPUTS: u32 = 18;
+pub const BSEC_INSTANCE_SIZE: u32 = 3272;
+pub const BSEC_BME_SLEEP_MODE: u32 = 0;
+pub const BSEC_BME_FORCED_MODE: u32 = 1;
+pub const BSEC_BME_PARALLEL_MODE: u32 = 2;
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum bsec_physical_sensor_t {
+    BSEC_INPUT_PRESSURE = 1,
+    BSEC_INPUT_HUMIDITY = 2,
+    BSEC_INPUT_TEMPERATURE = 3,
+    BSEC_INPUT_GASRESISTOR = 4,
+    BSEC_INPUT_HEATSOURCE = 14,
+    BSEC_INPUT_DISABLE_BASELINE_TRACKER = 23,
+    BSEC_INPUT_PROFILE_PART = 24,
+}
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum bsec_virtual_sensor_t {
+    BSEC_OUTPUT_IAQ = 1,
+    BSEC_OUTPUT_STATIC_IAQ = 2,
+    BSEC_OUTPUT_CO2_EQUIVALENT = 3,
+    BSEC_OUTPUT_BREATH_VOC_EQUIVALENT = 4,
+    BSEC_OUTPUT_RAW_TEMPERATURE = 6,
+    BSEC_OUTPUT_RAW_PRESSURE = 7,
+    BSEC_OUTPUT_RAW_HUMIDITY = 8,
+    BSEC_OUTPUT_RAW_GAS = 9,
+    BSEC_OUTPUT_STABILIZATION_STATUS = 12,
+    BSEC_OUTPUT_RUN_IN_STATUS = 13,
+    BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_TEMPERATURE = 14,
+    BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_HUMIDITY = 15,
+    BSEC_OUTPUT_GAS_PERCENTAGE = 21,
+    BSEC_OUTPUT_GAS_ESTIMATE_1 = 22,
+    BSEC_OUTPUT_GAS_ESTIMATE_2 = 23,
+    BSEC_OUTPUT_GAS_ESTIMATE_3 = 24,
+    BSEC_OUTPUT_GAS_ESTIMATE_4 = 25,
+    BSEC_OUTPUT_RAW_GAS_INDEX = 26,
+}
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum bsec_library_return_t {
+    BSEC_OK = 0,
+    BSEC_E_DOSTEPS_INVALIDINPUT = -1,
+    BSEC_E_DOSTEPS_VALUELIMITS = -2,
+    BSEC_E_DOSTEPS_DUPLICATEINPUT = -6,
+    BSEC_I_DOSTEPS_NOOUTPUTSRETURNABLE = 2,
+    BSEC_W_DOSTEPS_EXCESSOUTPUTS = 3,
+    BSEC_W_DOSTEPS_TSINTRADIFFOUTOFRANGE = 4,
+    BSEC_W_DOSTEPS_GASINDEXMISS = 5,
+    BSEC_E_SU_WRONGDATARATE = -10,
+    BSEC_E_SU_SAMPLERATELIMITS = -12,
+    BSEC_E_SU_DUPLICATEGATE = -13,
+    BSEC_E_SU_INVALIDSAMPLERATE = -14,
+    BSEC_E_SU_GATECOUNTEXCEEDSARRAY = -15,
+    BSEC_E_SU_SAMPLINTVLINTEGERMULT = -16,
+    BSEC_E_SU_MULTGASSAMPLINTVL = -17,
+    BSEC_E_SU_HIGHHEATERONDURATION = -18,
+    BSEC_W_SU_UNKNOWNOUTPUTGATE = 10,
+    BSEC_W_SU_MODINNOULP = 11,
+    BSEC_I_SU_SUBSCRIBEDOUTPUTGATES = 12,
+    BSEC_I_SU_GASESTIMATEPRECEDENCE = 13,
+    BSEC_W_SU_SAMPLERATEMISMATCH = 14,
+    BSEC_E_PARSE_SECTIONEXCEEDSWORKBUFFER = -32,
+    BSEC_E_CONFIG_FAIL = -33,
+    BSEC_E_CONFIG_VERSIONMISMATCH = -34,
+    BSEC_E_CONFIG_FEATUREMISMATCH = -35,
+    BSEC_E_CONFIG_CRCMISMATCH = -36,
+    BSEC_E_CONFIG_EMPTY = -37,
+    BSEC_E_CONFIG_INSUFFICIENTWORKBUFFER = -38,
+    BSEC_E_CONFIG_INVALIDSTRINGSIZE = -40,
+    BSEC_E_CONFIG_INSUFFICIENTBUFFER = -41,
+    BSEC_E_SET_INVALIDCHANNELIDENTIFIER = -100,
+    BSEC_E_SET_INVALIDLENGTH = -104,
+    BSEC_W_SC_CALL_TIMING_VIOLATION = 100,
+    BSEC_W_SC_MODEXCEEDULPTIMELIMIT = 101,
+    BSEC_W_SC_MODINSUFFICIENTWAITTIME = 102,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct bsec_version_t {
+    pub major: u8,
+    pub minor: u8,
+    pub major_bugfix: u8,
+    pub minor_bugfix: u8,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct bsec_input_t {
+    pub time_stamp: i64,
+    pub signal: f32,
+    pub signal_dimensions: u8,
+    pub sensor_id: u8,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct bsec_output_t {
+    pub time_stamp: i64,
+    pub signal: f32,
+    pub signal_dimensions: u8,
+    pub sensor_id: u8,
+    pub accuracy: u8,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct bsec_sensor_configuration_t {
+    pub sample_rate: f32,
+    pub sensor_id: u8,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct bsec_bme_settings_t {
+    pub next_call: i64,
+    pub process_data: u32,
+    pub heater_temperature: u16,
+    pub heater_duration: u16,
+    pub heater_temperature_profile: [u16; 10usize],
+    pub heater_duration_profile: [u16; 10usize],
+    pub heater_profile_len: u8,
+    pub run_gas: u8,
+    pub pressure_oversampling: u8,
+    pub temperature_oversampling: u8,
+    pub humidity_oversampling: u8,
+    pub trigger_measurement: u8,
+    pub op_mode: u8,
+}
+extern "C" {
+    pub fn bsec_get_version(bsec_version_p: *mut bsec_version_t) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_init() -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_do_steps(
+        inputs: *const bsec_input_t,
+        n_inputs: u8,
+        outputs: *mut bsec_output_t,
+        n_outputs: *mut u8,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_reset_output(sensor_id: u8) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_update_subscription(
+        requested_virtual_sensors: *const bsec_sensor_configuration_t,
+        n_requested_virtual_sensors: u8,
+        required_sensor_settings: *mut bsec_sensor_configuration_t,
+        n_required_sensor_settings: *mut u8,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_sensor_control(
+        time_stamp: i64,
+        sensor_settings: *mut bsec_bme_settings_t,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_set_configuration(
+        serialized_settings: *const u8,
+        n_serialized_settings: u32,
+        work_buffer: *mut u8,
+        n_work_buffer_size: u32,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_set_state(
+        serialized_state: *const u8,
+        n_serialized_state: u32,
+        work_buffer: *mut u8,
+        n_work_buffer_size: u32,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_get_configuration(
+        config_id: u8,
+        serialized_settings: *mut u8,
+        n_serialized_settings_max: u32,
+        work_buffer: *mut u8,
+        n_work_buffer: u32,
+        n_serialized_settings: *mut u32,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_get_state(
+        state_set_id: u8,
+        serialized_state: *mut u8,
+        n_serialized_state_max: u32,
+        work_buffer: *mut u8,
+        n_work_buffer: u32,
+        n_serialized_state: *mut u32,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_get_instance_size_m() -> usize;
+}
+extern "C" {
+    pub fn bsec_get_version_m(
+        inst: *mut cty::c_void,
+        bsec_version_p: *mut bsec_version_t,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_init_m(inst: *mut cty::c_void) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_do_steps_m(
+        inst: *mut cty::c_void,
+        inputs: *const bsec_input_t,
+        n_inputs: u8,
+        outputs: *mut bsec_output_t,
+        n_outputs: *mut u8,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_reset_output_m(inst: *mut cty::c_void, sensor_id: u8) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_update_subscription_m(
+        inst: *mut cty::c_void,
+        requested_virtual_sensors: *const bsec_sensor_configuration_t,
+        n_requested_virtual_sensors: u8,
+        required_sensor_settings: *mut bsec_sensor_configuration_t,
+        n_required_sensor_settings: *mut u8,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_sensor_control_m(
+        inst: *mut cty::c_void,
+        time_stamp: i64,
+        sensor_settings: *mut bsec_bme_settings_t,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_set_configuration_m(
+        inst: *mut cty::c_void,
+        serialized_settings: *const u8,
+        n_serialized_settings: u32,
+        work_buffer: *mut u8,
+        n_work_buffer_size: u32,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_set_state_m(
+        inst: *mut cty::c_void,
+        serialized_state: *const u8,
+        n_serialized_state: u32,
+        work_buffer: *mut u8,
+        n_work_buffer_size: u32,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_get_configuration_m(
+        inst: *mut cty::c_void,
+        config_id: u8,
+        serialized_settings: *mut u8,
+        n_serialized_settings_max: u32,
+        work_buffer: *mut u8,
+        n_work_buffer: u32,
+        n_serialized_settings: *mut u32,
+    ) -> bsec_library_return_t;
+}
+extern "C" {
+    pub fn bsec_get_state_m(
+        inst: *mut cty::c_void,
+        state_set_id: u8,
+        serialized_state: *mut u8,
+        n_serialized_state_max: u32,
+        work_buffer: *mut u8,
+        n_work_buffer: u32,
+        n_serialized_state: *mut u32,
+    ) -> bsec_library_return_t;
+}
@@ -0,0 +1,494 @@
+//! Registers, calibration and compensation of the BME680.
+//!
+//! This only contains the parts which don't require I/O, the formulas are the floating point
+//! variants of the Bosch reference driver.
+
+/// The I2C address of the sensor.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Address {
+    /// SDO connected to GND.
+    Primary = 0x76,
+    /// SDO connected to VDD.
+    Secondary = 0x77,
+}
+
+pub(crate) const CHIP_ID: u8 = 0x61;
+
+pub(crate) const REG_RES_HEAT_VAL: u8 = 0x00;
+pub(crate) const REG_RES_HEAT_RANGE: u8 = 0x02;
+pub(crate) const REG_RANGE_SW_ERR: u8 = 0x04;
+pub(crate) const REG_FIELD_0: u8 = 0x1D;
+pub(crate) const REG_RES_HEAT_0: u8 = 0x5A;
+pub(crate) const REG_GAS_WAIT_0: u8 = 0x64;
+pub(crate) const REG_CTRL_GAS_0: u8 = 0x70;
+pub(crate) const REG_CTRL_GAS_1: u8 = 0x71;
+pub(crate) const REG_CTRL_HUM: u8 = 0x72;
+pub(crate) const REG_CTRL_MEAS: u8 = 0x74;
+pub(crate) const REG_CONFIG: u8 = 0x75;
+pub(crate) const REG_COEFF_1: u8 = 0x89;
+pub(crate) const REG_ID: u8 = 0xD0;
+pub(crate) const REG_RESET: u8 = 0xE0;
+pub(crate) const REG_COEFF_2: u8 = 0xE1;
+
+pub(crate) const COEFF_1_LEN: usize = 25;
+pub(crate) const COEFF_2_LEN: usize = 16;
+pub(crate) const FIELD_LEN: usize = 15;
+
+pub(crate) const SOFT_RESET: u8 = 0xB6;
+pub(crate) const MODE_FORCED: u8 = 0b01;
+#[cfg(feature = "simulator")]
+pub(crate) const MODE_MASK: u8 = 0b11;
+pub(crate) const RUN_GAS: u8 = 0x10;
+pub(crate) const HEAT_OFF: u8 = 0x08;
+/// IIR filter with a coefficient of 3.
+#[cfg(feature = "async")]
+pub(crate) const FILTER_3: u8 = 0b010;
+
+pub(crate) const NEW_DATA: u8 = 0x80;
+pub(crate) const GAS_VALID: u8 = 0x20;
+pub(crate) const HEAT_STAB: u8 = 0x10;
+
+/// The number of measurement cycles of an oversampling register value.
+#[cfg(feature = "async")]
+fn cycles(oversampling: u8) -> u32 {
+    match oversampling {
+        0 => 0,
+        1..=5 => 1 << (oversampling - 1),
+        _ => 16,
+    }
+}
+
+/// The duration of a TPH measurement, in milliseconds, excluding the heating of the gas sensor.
+#[cfg(feature = "async")]
+pub(crate) fn measurement_duration(osrs_t: u8, osrs_p: u8, osrs_h: u8) -> u32 {
+    let cycles = cycles(osrs_t) + cycles(osrs_p) + cycles(osrs_h);
+    // measurement cycles, switching between T, P, H and the gas measurement, plus wake up
+    let micros = cycles * 1963 + 477 * 4 + 477 * 5;
+    (micros + 500) / 1000 + 1
+}
+
+/// Encode the heating duration for the `gas_wait_x` register.
+#[cfg(feature = "async")]
+pub(crate) fn gas_wait(duration_ms: u16) -> u8 {
+    if duration_ms >= 0xFC0 {
+        return 0xFF;
+    }
+
+    let mut duration = duration_ms;
+    let mut factor = 0u8;
+    while duration > 0x3F {
+        duration /= 4;
+        factor += 1;
+    }
+    duration as u8 + factor * 64
+}
+
+/// Decode the `gas_wait_x` register, to a duration in milliseconds.
+#[cfg(feature = "simulator")]
+pub(crate) fn gas_wait_duration(value: u8) -> u32 {
+    ((value & 0x3F) as u32) << (2 * (value >> 6) as u32)
+}
+
+/// Calibration parameters, stored in the non-volatile memory of the sensor.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct Calibration {
+    pub t1: u16,
+    pub t2: i16,
+    pub t3: i8,
+    pub p1: u16,
+    pub p2: i16,
+    pub p3: i8,
+    pub p4: i16,
+    pub p5: i16,
+    pub p6: i8,
+    pub p7: i8,
+    pub p8: i16,
+    pub p9: i16,
+    pub p10: u8,
+    pub h1: u16,
+    pub h2: u16,
+    pub h3: i8,
+    pub h4: i8,
+    pub h5: i8,
+    pub h6: u8,
+    pub h7: i8,
+    pub gh1: i8,
+    pub gh2: i16,
+    pub gh3: i8,
+    pub res_heat_range: u8,
+    pub res_heat_val: i8,
+    pub range_sw_err: i8,
+}
+
+impl Calibration {
+    /// The calibration of a typical sensor.
+    #[cfg(feature = "simulator")]
+    pub const TYPICAL: Calibration = Calibration {
+        t1: 25942,
+        t2: 26531,
+        t3: 3,
+        p1: 36418,
+        p2: -10464,
+        p3: 88,
+        p4: 7345,
+        p5: -149,
+        p6: 30,
+        p7: 58,
+        p8: -2836,
+        p9: -2498,
+        p10: 30,
+        h1: 830,
+        h2: 1004,
+        h3: 0,
+        h4: 45,
+        h5: 20,
+        h6: 120,
+        h7: -100,
+        gh1: -30,
+        gh2: -5969,
+        gh3: 18,
+        res_heat_range: 1,
+        res_heat_val: 48,
+        range_sw_err: 0,
+    };
+
+    /// Parse the calibration from the two coefficient blocks, and the heater registers.
+    #[cfg(feature = "async")]
+    pub fn parse(
+        coeff_1: &[u8; COEFF_1_LEN],
+        coeff_2: &[u8; COEFF_2_LEN],
+        res_heat_range: u8,
+        res_heat_val: u8,
+        range_sw_err: u8,
+    ) -> Self {
+        let mut c = [0u8; COEFF_1_LEN + COEFF_2_LEN];
+        c[..COEFF_1_LEN].copy_from_slice(coeff_1);
+        c[COEFF_1_LEN..].copy_from_slice(coeff_2);
+
+        let u16_at = |lsb: usize| u16::from_le_bytes([c[lsb], c[lsb + 1]]);
+        let i16_at = |lsb: usize| u16_at(lsb) as i16;
+
+        Calibration {
+            t1: u16_at(33),
+            t2: i16_at(1),
+            t3: c[3] as i8,
+            p1: u16_at(5),
+            p2: i16_at(7),
+            p3: c[9] as i8,
+            p4: i16_at(11),
+            p5: i16_at(13),
+            p6: c[16] as i8,
+            p7: c[15] as i8,
+            p8: i16_at(19),
+            p9: i16_at(21),
+            p10: c[23],
+            h1: ((c[27] as u16) << 4) | (c[26] & 0x0F) as u16,
+            h2: ((c[25] as u16) << 4) | (c[26] >> 4) as u16,
+            h3: c[28] as i8,
+            h4: c[29] as i8,
+            h5: c[30] as i8,
+            h6: c[31],
+            h7: c[32] as i8,
+            gh1: c[37] as i8,
+            gh2: i16_at(35),
+            gh3: c[38] as i8,
+            res_heat_range: (res_heat_range & 0x30) >> 4,
+            res_heat_val: res_heat_val as i8,
+            range_sw_err: (range_sw_err as i8 & 0xF0u8 as i8) / 16,
+        }
+    }
+
+    /// Encode the calibration into the two coefficient blocks, the reverse of [`Self::parse`].
+    #[cfg(feature = "simulator")]
+    pub fn coefficients(&self) -> ([u8; COEFF_1_LEN], [u8; COEFF_2_LEN]) {
+        let mut c = [0u8; COEFF_1_LEN + COEFF_2_LEN];
+
+        let mut u16_at =
+            |lsb: usize, value: u16| c[lsb..lsb + 2].copy_from_slice(&value.to_le_bytes());
+        u16_at(33, self.t1);
+        u16_at(1, self.t2 as u16);
+        u16_at(5, self.p1);
+        u16_at(7, self.p2 as u16);
+        u16_at(11, self.p4 as u16);
+        u16_at(13, self.p5 as u16);
+        u16_at(19, self.p8 as u16);
+        u16_at(21, self.p9 as u16);
+        u16_at(35, self.gh2 as u16);
+
+        c[3] = self.t3 as u8;
+        c[9] = self.p3 as u8;
+        c[15] = self.p7 as u8;
+        c[16] = self.p6 as u8;
+        c[23] = self.p10;
+        c[25] = (self.h2 >> 4) as u8;
+        c[26] = (((self.h2 & 0x0F) as u8) << 4) | (self.h1 & 0x0F) as u8;
+        c[27] = (self.h1 >> 4) as u8;
+        c[28] = self.h3 as u8;
+        c[29] = self.h4 as u8;
+        c[30] = self.h5 as u8;
+        c[31] = self.h6;
+        c[32] = self.h7 as u8;
+        c[37] = self.gh1 as u8;
+        c[38] = self.gh3 as u8;
+
+        let mut coeff_1 = [0u8; COEFF_1_LEN];
+        let mut coeff_2 = [0u8; COEFF_2_LEN];
+        coeff_1.copy_from_slice(&c[..COEFF_1_LEN]);
+        coeff_2.copy_from_slice(&c[COEFF_1_LEN..]);
+        (coeff_1, coeff_2)
+    }
+
+    /// Compensate the raw temperature, returning `t_fine`, which is used by the other formulas.
+    pub fn t_fine(&self, adc: u32) -> f32 {
+        let adc = adc as f32;
+        let t1 = self.t1 as f32;
+        let var1 = ((adc / 16384.0) - (t1 / 1024.0)) * self.t2 as f32;
+        let var2 = ((adc / 131072.0) - (t1 / 8192.0))
+            * ((adc / 131072.0) - (t1 / 8192.0))
+            * (self.t3 as f32 * 16.0);
+        var1 + var2
+    }
+
+    /// The temperature, in °C.
+    pub fn temperature(t_fine: f32) -> f32 {
+        t_fine / 5120.0
+    }
+
+    /// The pressure, in Pa.
+    pub fn pressure(&self, t_fine: f32, adc: u32) -> f32 {
+        let mut var1 = (t_fine / 2.0) - 64000.0;
+        let mut var2 = var1 * var1 * (self.p6 as f32 / 131072.0);
+        var2 += var1 * self.p5 as f32 * 2.0;
+        var2 = (var2 / 4.0) + (self.p4 as f32 * 65536.0);
+        var1 = (((self.p3 as f32 * var1 * var1) / 16384.0) + (self.p2 as f32 * var1)) / 524288.0;
+        var1 = (1.0 + (var1 / 32768.0)) * self.p1 as f32;
+
+        if var1 == 0.0 {
+            return 0.0;
+        }
+
+        let mut pressure = 1048576.0 - adc as f32;
+        pressure = ((pressure - (var2 / 4096.0)) * 6250.0) / var1;
+        let var1 = (self.p9 as f32 * pressure * pressure) / 2147483648.0;
+        let var2 = pressure * (self.p8 as f32 / 32768.0);
+        let var3 = (pressure / 256.0)
+            * (pressure / 256.0)
+            * (pressure / 256.0)
+            * (self.p10 as f32 / 131072.0);
+        pressure + (var1 + var2 + var3 + (self.p7 as f32 * 128.0)) / 16.0
+    }
+
+    /// The relative humidity, in %.
+    pub fn humidity(&self, t_fine: f32, adc: u16) -> f32 {
+        let temperature = Self::temperature(t_fine);
+        let var1 = adc as f32 - ((self.h1 as f32 * 16.0) + ((self.h3 as f32 / 2.0) * temperature));
+        let var2 = var1
+            * ((self.h2 as f32 / 262144.0)
+                * (1.0
+                    + ((self.h4 as f32 / 16384.0) * temperature)
+                    + ((self.h5 as f32 / 1048576.0) * temperature * temperature)));
+        let var3 = self.h6 as f32 / 16384.0;
+        let var4 = self.h7 as f32 / 2097152.0;
+        let humidity = var2 + ((var3 + (var4 * temperature)) * var2 * var2);
+        humidity.clamp(0.0, 100.0)
+    }
+
+    /// The gas resistance, in Ohm.
+    pub fn gas_resistance(&self, adc: u16, range: u8) -> f32 {
+        let range = (range & 0x0F) as usize;
+        let var1 = 1340.0 + (5.0 * self.range_sw_err as f32);
+        let var2 = var1 * (1.0 + GAS_RANGE_K1[range] / 100.0);
+        let var3 = 1.0 + (GAS_RANGE_K2[range] / 100.0);
+        1.0 / (var3 * 0.000000125 * (1u32 << range) as f32 * (((adc as f32 - 512.0) / var2) + 1.0))
+    }
+
+    /// The value of the `res_heat_x` register, for the target temperature of the heater.
+    pub fn res_heat(&self, target: u16, ambient: f32) -> u8 {
+        let target = (target as f32).min(400.0);
+        let var1 = (self.gh1 as f32 / 16.0) + 49.0;
+        let var2 = ((self.gh2 as f32 / 32768.0) * 0.0005) + 0.00235;
+        let var3 = self.gh3 as f32 / 1024.0;
+        let var4 = var1 * (1.0 + (var2 * target));
+        let var5 = var4 + (var3 * ambient);
+        let res_heat = 3.4
+            * ((var5
+                * (4.0 / (4.0 + self.res_heat_range as f32))
+                * (1.0 / (1.0 + (self.res_heat_val as f32 * 0.002))))
+                - 25.0);
+        res_heat.clamp(0.0, 255.0) as u8
+    }
+}
+
+const GAS_RANGE_K1: [f32; 16] = [
+    0.0, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, -0.8, 0.0, 0.0, -0.2, -0.5, 0.0, -1.0, 0.0, 0.0,
+];
+const GAS_RANGE_K2: [f32; 16] = [
+    0.0, 0.0, 0.0, 0.0, 0.1, 0.7, 0.0, -0.8, -0.1, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+];
+
+/// The raw values of the field data registers.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct FieldData {
+    pub status: u8,
+    pub pressure: u32,
+    pub temperature: u32,
+    pub humidity: u16,
+    pub gas: u16,
+    pub gas_range: u8,
+    pub gas_valid: bool,
+    pub heat_stable: bool,
+}
+
+impl FieldData {
+    #[cfg(feature = "async")]
+    pub fn parse(buf: &[u8; FIELD_LEN]) -> Self {
+        FieldData {
+            status: buf[0],
+            pressure: ((buf[2] as u32) << 12) | ((buf[3] as u32) << 4) | ((buf[4] as u32) >> 4),
+            temperature: ((buf[5] as u32) << 12) | ((buf[6] as u32) << 4) | ((buf[7] as u32) >> 4),
+            humidity: ((buf[8] as u16) << 8) | buf[9] as u16,
+            gas: ((buf[13] as u16) << 2) | ((buf[14] as u16) >> 6),
+            gas_range: buf[14] & 0x0F,
+            gas_valid: buf[14] & GAS_VALID != 0,
+            heat_stable: buf[14] & HEAT_STAB != 0,
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub fn has_new_data(&self) -> bool {
+        self.status & NEW_DATA != 0
+    }
+
+    /// Encode the field data registers, the reverse of [`Self::parse`].
+    #[cfg(feature = "simulator")]
+    pub fn encode(&self) -> [u8; FIELD_LEN] {
+        let mut buf = [0u8; FIELD_LEN];
+        buf[0] = self.status;
+        buf[2] = (self.pressure >> 12) as u8;
+        buf[3] = (self.pressure >> 4) as u8;
+        buf[4] = ((self.pressure & 0x0F) << 4) as u8;
+        buf[5] = (self.temperature >> 12) as u8;
+        buf[6] = (self.temperature >> 4) as u8;
+        buf[7] = ((self.temperature & 0x0F) << 4) as u8;
+        buf[8] = (self.humidity >> 8) as u8;
+        buf[9] = self.humidity as u8;
+        buf[13] = (self.gas >> 2) as u8;
+        buf[14] = (((self.gas & 0x03) as u8) << 6) | (self.gas_range & 0x0F);
+        if self.gas_valid {
+            buf[14] |= GAS_VALID;
+        }
+        if self.heat_stable {
+            buf[14] |= HEAT_STAB;
+        }
+        buf
+    }
+}
+
+#[cfg(all(test, feature = "async", feature = "simulator"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calibration_round_trip() {
+        let calibration = Calibration::TYPICAL;
+        let (coeff_1, coeff_2) = calibration.coefficients();
+        let parsed = Calibration::parse(
+            &coeff_1,
+            &coeff_2,
+            calibration.res_heat_range << 4,
+            calibration.res_heat_val as u8,
+            (calibration.range_sw_err << 4) as u8,
+        );
+        assert_eq!(parsed, calibration);
+    }
+
+    #[test]
+    fn calibration_round_trip_extremes() {
+        let calibration = Calibration {
+            t1: u16::MAX,
+            t2: i16::MIN,
+            t3: i8::MIN,
+            p1: 1,
+            p2: -1,
+            p3: i8::MAX,
+            p4: i16::MAX,
+            p5: -2,
+            p6: -3,
+            p7: -4,
+            p8: i16::MIN,
+            p9: 5,
+            p10: u8::MAX,
+            // 12 bits each, sharing a register
+            h1: 0xFFF,
+            h2: 0xABC,
+            h3: -5,
+            h4: -6,
+            h5: -7,
+            h6: u8::MAX,
+            h7: i8::MIN,
+            gh1: i8::MIN,
+            gh2: -8,
+            gh3: i8::MAX,
+            res_heat_range: 3,
+            res_heat_val: -9,
+            range_sw_err: -8,
+        };
+        let (coeff_1, coeff_2) = calibration.coefficients();
+        let parsed = Calibration::parse(
+            &coeff_1,
+            &coeff_2,
+            calibration.res_heat_range << 4,
+            calibration.res_heat_val as u8,
+            (calibration.range_sw_err << 4) as u8,
+        );
+        assert_eq!(parsed, calibration);
+    }
+
+    #[test]
+    fn field_data_round_trip() {
+        for field in &[
+            FieldData::default(),
+            FieldData {
+                status: NEW_DATA,
+                pressure: 0x5_1234,
+                temperature: 0x7_ABCD,
+                humidity: 0x6543,
+                gas: 0x2A5,
+                gas_range: 7,
+                gas_valid: true,
+                heat_stable: false,
+            },
+            FieldData {
+                status: NEW_DATA,
+                pressure: 0xF_FFFF,
+                temperature: 0xF_FFFF,
+                humidity: 0xFFFF,
+                gas: 0x3FF,
+                gas_range: 0x0F,
+                gas_valid: true,
+                heat_stable: true,
+            },
+        ] {
+            let parsed = FieldData::parse(&field.encode());
+            assert_eq!(&parsed, field);
+            assert_eq!(parsed.has_new_data(), field.status & NEW_DATA != 0);
+        }
+    }
+
+    #[test]
+    fn gas_wait_round_trip() {
+        for duration in &[0u16, 1, 63, 64, 100, 150, 197, 1000, 4000] {
+            let decoded = gas_wait_duration(gas_wait(*duration));
+            // the factor loses the lower bits of long durations
+            assert!(decoded <= *duration as u32, "{}: {}", duration, decoded);
+            assert!(
+                decoded * 100 >= *duration as u32 * 90,
+                "{}: {}",
+                duration,
+                decoded
+            );
+        }
+        assert_eq!(gas_wait(u16::MAX), 0xFF);
+    }
+}
@@ -1,3 +1,13 @@
+//! Shims for the C math functions, referenced by the BSEC library.
+//!
+//! Bare metal targets don't have a C library providing those symbols, so we provide them, backed by `libm`. On
+//! targets with a C library (or with the `libm-shims` feature disabled) this module isn't compiled, as the symbols
+//! would collide with the ones from the C library.
+//!
+//! The build script checks the undefined symbols of the selected library against this list, so when adding a new
+//! variant, it will warn about any missing shim. The `libm_shims` test does the same for all bare metal variants, and
+//! fails on any missing shim.
+
 /// Exported pow(double, double)
 #[no_mangle]
 pub extern "C" fn pow(x: cty::c_double, y: cty::c_double) -> cty::c_double {
@@ -99,3 +109,105 @@ pub extern "C" fn fminf(x: cty::c_float, y: cty::c_float) -> cty::c_float {
 pub extern "C" fn fmaxf(x: cty::c_float, y: cty::c_float) -> cty::c_float {
     libm::fmaxf(x, y)
 }
+
+/// Exported exp(double)
+#[no_mangle]
+pub extern "C" fn exp(x: cty::c_double) -> cty::c_double {
+    libm::exp(x)
+}
+
+/// Exported log(double)
+#[no_mangle]
+pub extern "C" fn log(x: cty::c_double) -> cty::c_double {
+    libm::log(x)
+}
+
+/// Exported log10(double)
+#[no_mangle]
+pub extern "C" fn log10(x: cty::c_double) -> cty::c_double {
+    libm::log10(x)
+}
+
+/// Exported round(double)
+#[no_mangle]
+pub extern "C" fn round(x: cty::c_double) -> cty::c_double {
+    libm::round(x)
+}
+
+/// Exported trunc(double)
+#[no_mangle]
+pub extern "C" fn trunc(x: cty::c_double) -> cty::c_double {
+    libm::trunc(x)
+}
+
+/// Exported fmax(double, double)
+#[no_mangle]
+pub extern "C" fn fmax(x: cty::c_double, y: cty::c_double) -> cty::c_double {
+    libm::fmax(x, y)
+}
+
+/// Exported fmin(double, double)
+#[no_mangle]
+pub extern "C" fn fmin(x: cty::c_double, y: cty::c_double) -> cty::c_double {
+    libm::fmin(x, y)
+}
+
+/// Exported fmod(double, double)
+#[no_mangle]
+pub extern "C" fn fmod(x: cty::c_double, y: cty::c_double) -> cty::c_double {
+    libm::fmod(x, y)
+}
+
+/// Exported log2f(float)
+#[no_mangle]
+pub extern "C" fn log2f(x: cty::c_float) -> cty::c_float {
+    libm::log2f(x)
+}
+
+/// Exported exp2f(float)
+#[no_mangle]
+pub extern "C" fn exp2f(x: cty::c_float) -> cty::c_float {
+    libm::exp2f(x)
+}
+
+/// Exported cosf(float)
+#[no_mangle]
+pub extern "C" fn cosf(x: cty::c_float) -> cty::c_float {
+    libm::cosf(x)
+}
+
+/// Exported tanf(float)
+#[no_mangle]
+pub extern "C" fn tanf(x: cty::c_float) -> cty::c_float {
+    libm::tanf(x)
+}
+
+/// Exported atanf(float)
+#[no_mangle]
+pub extern "C" fn atanf(x: cty::c_float) -> cty::c_float {
+    libm::atanf(x)
+}
+
+/// Exported tanhf(float)
+#[no_mangle]
+pub extern "C" fn tanhf(x: cty::c_float) -> cty::c_float {
+    libm::tanhf(x)
+}
+
+/// Exported truncf(float)
+#[no_mangle]
+pub extern "C" fn truncf(x: cty::c_float) -> cty::c_float {
+    libm::truncf(x)
+}
+
+/// Exported fmodf(float, float)
+#[no_mangle]
+pub extern "C" fn fmodf(x: cty::c_float, y: cty::c_float) -> cty::c_float {
+    libm::fmodf(x, y)
+}
+
+/// Exported atan2f(float, float)
+#[no_mangle]
+pub extern "C" fn atan2f(x: cty::c_float, y: cty::c_float) -> cty::c_float {
+    libm::atan2f(x, y)
+}
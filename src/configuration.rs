@@ -0,0 +1,10 @@
+//! Serialized configurations, shipped with the BSEC library.
+//!
+//! The constants are generated from the `config` directory of the BSEC library, and named after
+//! the settings they represent: supply voltage, sample interval and history used for the
+//! IAQ calibration. For example, `GENERIC_33V_3S_4D` is for a 3.3V supply, a sample interval of
+//! 3 seconds, and a calibration history of 4 days.
+//!
+//! Use them with [`crate::Bsec::with_configuration`] or [`crate::Bsec::set_configuration`].
+
+include!(concat!(env!("OUT_DIR"), "/configurations.rs"));
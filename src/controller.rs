@@ -0,0 +1,140 @@
+//! The [`EnvironmentalSensor`] implementation for the `drogue-bme680` crate.
+
+use crate::{Control, EnvironmentalSensor, Measurement, Oversampling};
+use drogue_bme680::{AmbientTemperatureProvider, Bme680Controller, Configuration, Data, Filter};
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+use embedded_time::duration::Milliseconds;
+
+/// An error of the [`Bme680ControllerSensor`], telling which operation of the
+/// [`Bme680Controller`] failed. The error of the controller is logged.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Bme680ControllerError {
+    /// Applying the settings failed.
+    Configure,
+    /// Performing the measurement failed.
+    Measure,
+}
+
+/// A BME680, using the [`Bme680Controller`] of `drogue-bme680`.
+///
+/// The delay of the controller is also used for the [`DelayMs`] implementation, so that
+/// [`BsecDriver::sleep`](crate::BsecDriver::sleep) can use it.
+pub struct Bme680ControllerSensor<I2C, D, P>
+where
+    I2C: Read + Write + WriteRead,
+    D: DelayMs<Milliseconds>,
+    P: AmbientTemperatureProvider,
+{
+    controller: Bme680Controller<I2C, D, P>,
+    filter: Filter,
+    run_gas: bool,
+}
+
+impl<I2C, D, P> Bme680ControllerSensor<I2C, D, P>
+where
+    I2C: Read + Write + WriteRead,
+    D: DelayMs<Milliseconds>,
+    P: AmbientTemperatureProvider,
+{
+    pub fn new(controller: Bme680Controller<I2C, D, P>) -> Self {
+        Bme680ControllerSensor {
+            controller,
+            filter: Filter::Coefficient3,
+            run_gas: false,
+        }
+    }
+
+    /// Set the IIR filter of the sensor, used for all measurements.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Access the controller, e.g. to read the sensor outside of the BSEC loop.
+    pub fn controller(&mut self) -> &mut Bme680Controller<I2C, D, P> {
+        &mut self.controller
+    }
+
+    /// Release the controller.
+    pub fn into_inner(self) -> Bme680Controller<I2C, D, P> {
+        self.controller
+    }
+}
+
+impl<I2C, D, P> EnvironmentalSensor for Bme680ControllerSensor<I2C, D, P>
+where
+    I2C: Read + Write + WriteRead,
+    D: DelayMs<Milliseconds>,
+    P: AmbientTemperatureProvider,
+{
+    type Error = Bme680ControllerError;
+
+    fn configure(&mut self, control: &Control) -> Result<(), Self::Error> {
+        self.run_gas = control.run_gas;
+        self.controller
+            .update_configuration(Configuration {
+                filter: self.filter,
+                temperature: control.temperature_oversampling.into(),
+                pressure: control.pressure_oversampling.into(),
+                humidity: control.humidity_oversampling.into(),
+                heater_temperature: control.heater_temperature,
+                heater_duration: control.heating_duration,
+                run_gas: control.run_gas,
+            })
+            .map_err(|err| {
+                log::warn!("Failed to configure the sensor: {:?}", err);
+                Bme680ControllerError::Configure
+            })
+    }
+
+    fn measure(&mut self) -> Result<Option<Measurement>, Self::Error> {
+        let data = self.controller.measure_default().map_err(|err| {
+            log::warn!("Failed to measure: {:?}", err);
+            Bme680ControllerError::Measure
+        })?;
+        Ok(data.map(|data| Measurement {
+            gas_resistance: if self.run_gas {
+                Some(data.gas_resistance)
+            } else {
+                None
+            },
+            ..(&data).into()
+        }))
+    }
+}
+
+impl<I2C, D, P> DelayMs<Milliseconds> for Bme680ControllerSensor<I2C, D, P>
+where
+    I2C: Read + Write + WriteRead,
+    D: DelayMs<Milliseconds>,
+    P: AmbientTemperatureProvider,
+{
+    fn delay_ms(&mut self, ms: Milliseconds) {
+        self.controller.delay(ms);
+    }
+}
+
+impl From<Oversampling> for drogue_bme680::Oversampling {
+    fn from(value: Oversampling) -> Self {
+        match value {
+            Oversampling::Skipped => drogue_bme680::Oversampling::Skipped,
+            Oversampling::By1 => drogue_bme680::Oversampling::By1,
+            Oversampling::By2 => drogue_bme680::Oversampling::By2,
+            Oversampling::By4 => drogue_bme680::Oversampling::By4,
+            Oversampling::By8 => drogue_bme680::Oversampling::By8,
+            Oversampling::By16 => drogue_bme680::Oversampling::By16,
+        }
+    }
+}
+
+impl From<&Data> for Measurement {
+    fn from(data: &Data) -> Self {
+        Measurement {
+            temperature: data.temperature,
+            humidity: data.humidity,
+            pressure: data.pressure,
+            gas_resistance: Some(data.gas_resistance),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! A driver, running the BSEC loop for an environmental sensor.
+
+use crate::{Bsec, Control, EnvironmentalSensor, Error, Measurement, Outputs, Timestamp};
+use core::convert::TryFrom;
+use core::fmt::{Debug, Display, Formatter};
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_time::duration::{Generic, Milliseconds, Nanoseconds};
+use embedded_time::Clock;
+
+/// An error of the [`BsecDriver`], with the error type `E` of the sensor.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DriverError<E> {
+    /// The BSEC library reported an error.
+    Bsec(Error),
+    /// Communicating with the sensor failed.
+    Sensor(E),
+    /// The clock failed to provide the current time.
+    Clock,
+}
+
+impl<E> From<Error> for DriverError<E> {
+    fn from(err: Error) -> Self {
+        DriverError::Bsec(err)
+    }
+}
+
+impl<E: Debug> Display for DriverError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DriverError::Bsec(err) => write!(f, "BSEC: {}", err),
+            DriverError::Sensor(err) => write!(f, "Failed to access the sensor: {:?}", err),
+            DriverError::Clock => write!(f, "Failed to read the clock"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: Debug> std::error::Error for DriverError<E> {}
+
+/// The result of polling the [`BsecDriver`].
+#[derive(Clone, Debug)]
+pub struct PollResult {
+    /// The measured data, if a measurement was performed.
+    pub data: Option<Measurement>,
+    /// The outputs, if a measurement was processed.
+    pub outputs: Option<Outputs>,
+    /// When the driver needs to be polled again.
+    pub next_call: Timestamp,
+}
+
+/// The state of a driver, shared by the blocking and the async one: the BSEC instance, the
+/// sensor, the clock and the schedule.
+pub(crate) struct DriverCore<'c, S, C: Clock> {
+    pub(crate) bsec: Bsec,
+    pub(crate) sensor: S,
+    clock: &'c C,
+    pub(crate) next_call: Timestamp,
+}
+
+impl<'c, S, C> DriverCore<'c, S, C>
+where
+    C: Clock,
+    Nanoseconds<u64>: TryFrom<Generic<C::T>>,
+{
+    pub(crate) fn new(bsec: Bsec, sensor: S, clock: &'c C) -> Self {
+        DriverCore {
+            bsec,
+            sensor,
+            clock,
+            next_call: Timestamp::default(),
+        }
+    }
+
+    pub(crate) fn now<E>(&self) -> Result<Timestamp, DriverError<E>> {
+        self.clock
+            .try_now()
+            .ok()
+            .and_then(|now| Timestamp::from_instant(&now))
+            .ok_or(DriverError::Clock)
+    }
+
+    /// Get the sensor settings, if a call is due.
+    ///
+    /// Returns the settings and the current time, if BSEC requests a measurement.
+    pub(crate) fn control<E>(&mut self) -> Result<Option<(Control, Timestamp)>, DriverError<E>> {
+        let now = self.now()?;
+        if now < self.next_call {
+            return Ok(None);
+        }
+
+        let control = self.bsec.sensor_control(now)?;
+        self.next_call = control.next_call;
+
+        Ok(Some((control, now)).filter(|(control, _)| control.trigger_measurement))
+    }
+
+    /// Process the measurement, which was requested by `control` at `now`.
+    pub(crate) fn process<E>(
+        &mut self,
+        control: &Control,
+        now: Timestamp,
+        measurement: &Measurement,
+    ) -> Result<Outputs, DriverError<E>> {
+        // BSEC expects the timestamp of when the measurement was triggered
+        let inputs = control.measurement_inputs(measurement);
+        Ok(self.bsec.process_data(now, &inputs)?)
+    }
+
+    /// The number of milliseconds until the next call is due, rounded up, or `None` if it is
+    /// already due.
+    pub(crate) fn time_to_next_call<E>(&self) -> Result<Option<u32>, DriverError<E>> {
+        Ok(self
+            .next_call
+            .checked_duration_since(self.now()?)
+            .filter(|diff| diff.0 > 0)
+            .map(|diff| {
+                let millis = diff.0 / 1_000_000 + u64::from(diff.0 % 1_000_000 != 0);
+                u32::try_from(millis).unwrap_or(u32::MAX)
+            }))
+    }
+}
+
+/// Implement the accessors of a driver, which keeps its [`DriverCore`] in the field `core`.
+macro_rules! driver_accessors {
+    () => {
+        /// The BSEC instance, e.g. to check the subscription.
+        pub fn bsec(&self) -> &Bsec {
+            &self.core.bsec
+        }
+
+        /// Access the BSEC instance, e.g. to change the subscription, or save the state.
+        pub fn bsec_mut(&mut self) -> &mut Bsec {
+            &mut self.core.bsec
+        }
+
+        /// Access the sensor, e.g. to read its state between measurements.
+        pub fn sensor(&mut self) -> &mut S {
+            &mut self.core.sensor
+        }
+
+        /// Release the BSEC instance and the sensor, e.g. to save the state before shutting down.
+        pub fn into_inner(self) -> (Bsec, S) {
+            (self.core.bsec, self.core.sensor)
+        }
+
+        /// When the driver needs to be polled next.
+        pub fn next_call(&self) -> Timestamp {
+            self.core.next_call
+        }
+
+        /// The current time, according to the clock.
+        pub fn now(&self) -> Result<Timestamp, DriverError<S::Error>> {
+            self.core.now()
+        }
+    };
+}
+
+#[cfg(feature = "async")]
+pub(crate) use driver_accessors;
+
+/// Runs the BSEC loop for an [`EnvironmentalSensor`]: getting the sensor settings from BSEC,
+/// applying them, measuring and processing the measured data.
+///
+/// ```ignore
+/// let sensor = Bme680ControllerSensor::new(controller);
+/// let mut driver = BsecDriver::new(bsec, sensor, &CLOCK);
+/// loop {
+///     let poll = driver.poll()?;
+///     if let Some(outputs) = poll.outputs {
+///         log::info!("IAQ: {:?}", outputs.iaq);
+///     }
+///     // sleep until `poll.next_call`
+/// }
+/// ```
+pub struct BsecDriver<'c, S, C>
+where
+    S: EnvironmentalSensor,
+    C: Clock,
+{
+    core: DriverCore<'c, S, C>,
+}
+
+impl<'c, S, C> BsecDriver<'c, S, C>
+where
+    S: EnvironmentalSensor,
+    C: Clock,
+    Nanoseconds<u64>: TryFrom<Generic<C::T>>,
+{
+    /// Create a new driver.
+    ///
+    /// The subscription of the `bsec` instance must already be set up.
+    pub fn new(bsec: Bsec, sensor: S, clock: &'c C) -> Self {
+        BsecDriver {
+            core: DriverCore::new(bsec, sensor, clock),
+        }
+    }
+
+    driver_accessors!();
+
+    /// Run a single iteration of the loop, if it is due.
+    ///
+    /// If it isn't time yet, nothing is done. If BSEC requests a measurement, the sensor is
+    /// configured, the measurement is performed, and the measured data gets processed.
+    pub fn poll(&mut self) -> Result<PollResult, DriverError<S::Error>> {
+        let (control, now) = match self.core.control()? {
+            Some(control) => control,
+            None => return Ok(self.idle()),
+        };
+
+        let sensor = &mut self.core.sensor;
+        sensor.configure(&control).map_err(DriverError::Sensor)?;
+        let data = match sensor.measure().map_err(DriverError::Sensor)? {
+            Some(data) => data,
+            None => {
+                log::warn!("Measurement didn't provide any data");
+                return Ok(self.idle());
+            }
+        };
+
+        let outputs = self.core.process(&control, now, &data)?;
+
+        Ok(PollResult {
+            data: Some(data),
+            outputs: Some(outputs),
+            next_call: self.core.next_call,
+        })
+    }
+
+    /// The time until the next call is due, rounded up to whole milliseconds, or `None` if it
+    /// is already due.
+    pub fn time_to_next_call(&self) -> Result<Option<Milliseconds>, DriverError<S::Error>> {
+        Ok(self.core.time_to_next_call()?.map(Milliseconds))
+    }
+
+    fn idle(&self) -> PollResult {
+        PollResult {
+            data: None,
+            outputs: None,
+            next_call: self.core.next_call,
+        }
+    }
+}
+
+impl<'c, S, C> BsecDriver<'c, S, C>
+where
+    S: EnvironmentalSensor + DelayMs<Milliseconds>,
+    C: Clock,
+    Nanoseconds<u64>: TryFrom<Generic<C::T>>,
+{
+    /// Block until the next call is due, using the delay of the sensor.
+    pub fn sleep(&mut self) -> Result<(), DriverError<S::Error>> {
+        if let Some(diff) = self.time_to_next_call()? {
+            self.core.sensor.delay_ms(diff);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use crate::ffi::bsec_library_return_t;
+use core::fmt::{Display, Formatter};
+
+use bsec_library_return_t::*;
+
+/// An error reported by the BSEC library.
+///
+/// The variant tells which operation failed, the inner value is the original return code.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Initializing the library failed.
+    Init(bsec_library_return_t),
+    /// Processing the input data, using `bsec_do_steps`, failed.
+    DoSteps(bsec_library_return_t),
+    /// Updating the subscription failed.
+    UpdateSubscription(bsec_library_return_t),
+    /// Retrieving the sensor settings failed.
+    SensorControl(bsec_library_return_t),
+    /// Serializing the state failed.
+    GetState(bsec_library_return_t),
+    /// Restoring the state failed.
+    SetState(bsec_library_return_t),
+    /// Applying the configuration failed.
+    SetConfiguration(bsec_library_return_t),
+}
+
+impl Error {
+    /// The original return code of the BSEC library.
+    pub fn code(&self) -> bsec_library_return_t {
+        match self {
+            Error::Init(code)
+            | Error::DoSteps(code)
+            | Error::UpdateSubscription(code)
+            | Error::SensorControl(code)
+            | Error::GetState(code)
+            | Error::SetState(code)
+            | Error::SetConfiguration(code) => *code,
+        }
+    }
+
+    fn operation(&self) -> &'static str {
+        match self {
+            Error::Init(_) => "init",
+            Error::DoSteps(_) => "do_steps",
+            Error::UpdateSubscription(_) => "update_subscription",
+            Error::SensorControl(_) => "sensor_control",
+            Error::GetState(_) => "get_state",
+            Error::SetState(_) => "set_state",
+            Error::SetConfiguration(_) => "set_configuration",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}: {}", self.operation(), describe(self.code()))
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// A warning, or informational message, reported by the BSEC library.
+///
+/// In contrast to an [`Error`], the outcome of the operation is still valid.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Warning(pub bsec_library_return_t);
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(describe(self.0))
+    }
+}
+
+/// Evaluate a return code.
+///
+/// Negative codes are errors, positive codes are warnings.
+pub(crate) fn check(
+    code: bsec_library_return_t,
+    error: fn(bsec_library_return_t) -> Error,
+) -> Result<Option<Warning>, Error> {
+    match code {
+        BSEC_OK => Ok(None),
+        code if (code as i32) > 0 => Ok(Some(Warning(code))),
+        code => Err(error(code)),
+    }
+}
+
+fn describe(code: bsec_library_return_t) -> &'static str {
+    match code {
+        BSEC_OK => "Function execution successful",
+        BSEC_E_DOSTEPS_INVALIDINPUT => "Input sensor id is not in the valid range, or not valid for the requested virtual sensor",
+        BSEC_E_DOSTEPS_VALUELIMITS => "Value of input sensor signal is not in the valid range",
+        BSEC_E_DOSTEPS_DUPLICATEINPUT => "Duplicate input sensor ids",
+        BSEC_I_DOSTEPS_NOOUTPUTSRETURNABLE => "No memory allocated to hold the outputs",
+        BSEC_W_DOSTEPS_EXCESSOUTPUTS => "Not enough memory allocated to hold all outputs",
+        BSEC_W_DOSTEPS_TSINTRADIFFOUTOFRANGE => "Duplicate timestamps of inputs",
+        #[cfg(feature = "bsec2")]
+        BSEC_W_DOSTEPS_GASINDEXMISS => "Gas index of the heater profile is missing",
+        BSEC_E_SU_WRONGDATARATE => "The sample rate of the requested virtual sensor is zero",
+        BSEC_E_SU_SAMPLERATELIMITS => "The sample rate of the requested virtual sensor is not allowed for that sensor",
+        BSEC_E_SU_DUPLICATEGATE => "Duplicate virtual sensor ids requested",
+        BSEC_E_SU_INVALIDSAMPLERATE => "The sample rate of the requested virtual sensor is outside the global limits",
+        BSEC_E_SU_GATECOUNTEXCEEDSARRAY => "Not enough memory allocated to hold the required physical sensors",
+        BSEC_E_SU_SAMPLINTVLINTEGERMULT => "The sample rate of the requested virtual sensor is not correct",
+        BSEC_E_SU_MULTGASSAMPLINTVL => "The sample rate of the requested virtual sensor does not match the rate of the gas sensor",
+        BSEC_E_SU_HIGHHEATERONDURATION => "The duration of one measurement is longer than the requested sampling interval",
+        BSEC_W_SU_UNKNOWNOUTPUTGATE => "Virtual sensor id is not in the valid range",
+        BSEC_W_SU_MODINNOULP => "ULP plus can not be requested in non-ULP mode",
+        BSEC_I_SU_SUBSCRIBEDOUTPUTGATES => "No virtual sensors were requested",
+        #[cfg(feature = "bsec2")]
+        BSEC_I_SU_GASESTIMATEPRECEDENCE => "Gas estimate outputs take precedence over the IAQ outputs",
+        #[cfg(feature = "bsec2")]
+        BSEC_W_SU_SAMPLERATEMISMATCH => "The sample rate of the gas estimate outputs does not match the scan mode",
+        BSEC_E_PARSE_SECTIONEXCEEDSWORKBUFFER => "Work buffer is not sufficient",
+        BSEC_E_CONFIG_FAIL => "Configuration failed",
+        BSEC_E_CONFIG_VERSIONMISMATCH => "Version of the serialized data does not match the library version",
+        BSEC_E_CONFIG_FEATUREMISMATCH => "Features of the serialized data do not match the library implementation",
+        BSEC_E_CONFIG_CRCMISMATCH => "Serialized data is corrupted",
+        BSEC_E_CONFIG_EMPTY => "Serialized data is too short to be valid",
+        BSEC_E_CONFIG_INSUFFICIENTWORKBUFFER => "Work buffer is not large enough",
+        BSEC_E_CONFIG_INVALIDSTRINGSIZE => "Size encoded in the serialized data does not match the actual size",
+        BSEC_E_CONFIG_INSUFFICIENTBUFFER => "Buffer is not large enough to hold the serialized data",
+        BSEC_E_SET_INVALIDCHANNELIDENTIFIER => "Invalid channel identifier",
+        BSEC_E_SET_INVALIDLENGTH => "Invalid length",
+        BSEC_W_SC_CALL_TIMING_VIOLATION => "Difference between actual and defined sampling intervals of sensor_control is too large",
+        BSEC_W_SC_MODEXCEEDULPTIMELIMIT => "ULP plus is not allowed because an ULP measurement just took or will take place",
+        BSEC_W_SC_MODINSUFFICIENTWAITTIME => "ULP plus is not allowed because not enough time passed since the last ULP plus",
+        #[allow(unreachable_patterns)]
+        _ => "Unknown return code",
+    }
+}
@@ -0,0 +1,105 @@
+use crate::ffi::*;
+#[cfg(feature = "serde")]
+use core::convert::TryFrom;
+
+/// Maximum number of steps in a heater profile.
+pub const MAX_HEATER_PROFILE_LEN: usize = 10;
+
+/// The operation mode of the BME688, requested by BSEC.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OperationMode {
+    /// No measurement is required.
+    Sleep,
+    /// Perform a single measurement, like with the BME680.
+    Forced,
+    /// Continuously cycle through the heater profile.
+    Parallel,
+}
+
+impl OperationMode {
+    pub(crate) fn from_raw(mode: u8) -> Self {
+        match mode as u32 {
+            BSEC_BME_FORCED_MODE => OperationMode::Forced,
+            BSEC_BME_PARALLEL_MODE => OperationMode::Parallel,
+            _ => OperationMode::Sleep,
+        }
+    }
+}
+
+/// A heater profile, consisting of steps of temperature and duration.
+///
+/// In forced mode, the durations are in milliseconds. In parallel mode, they are multiples of the
+/// time base of the sensor.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "HeaterProfileFields")
+)]
+pub struct HeaterProfile {
+    temperatures: [u16; MAX_HEATER_PROFILE_LEN],
+    durations: [u16; MAX_HEATER_PROFILE_LEN],
+    len: usize,
+}
+
+/// The fields of a [`HeaterProfile`], which are validated when deserializing.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct HeaterProfileFields {
+    temperatures: [u16; MAX_HEATER_PROFILE_LEN],
+    durations: [u16; MAX_HEATER_PROFILE_LEN],
+    len: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<HeaterProfileFields> for HeaterProfile {
+    type Error = &'static str;
+
+    fn try_from(fields: HeaterProfileFields) -> Result<Self, Self::Error> {
+        if fields.len > MAX_HEATER_PROFILE_LEN {
+            return Err("heater profile too long");
+        }
+        Ok(HeaterProfile {
+            temperatures: fields.temperatures,
+            durations: fields.durations,
+            len: fields.len,
+        })
+    }
+}
+
+impl HeaterProfile {
+    pub(crate) fn from_settings(settings: &bsec_bme_settings_t) -> Self {
+        HeaterProfile {
+            temperatures: settings.heater_temperature_profile,
+            durations: settings.heater_duration_profile,
+            len: (settings.heater_profile_len as usize).min(MAX_HEATER_PROFILE_LEN),
+        }
+    }
+
+    /// The heater temperatures, in °C.
+    pub fn temperatures(&self) -> &[u16] {
+        &self.temperatures[..self.len]
+    }
+
+    /// The durations of the heater steps.
+    pub fn durations(&self) -> &[u16] {
+        &self.durations[..self.len]
+    }
+
+    /// The steps of the profile, as pairs of temperature and duration.
+    pub fn steps(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.temperatures()
+            .iter()
+            .copied()
+            .zip(self.durations().iter().copied())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
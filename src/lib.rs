@@ -1,4 +1,4 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(clippy::redundant_static_lifetimes)]
 #![allow(clippy::type_complexity)]
 #![allow(clippy::too_many_arguments)]
@@ -8,28 +8,95 @@
 
 use core::fmt::{Display, Formatter};
 
+#[cfg(feature = "async")]
+mod asynch;
+#[cfg(any(feature = "async", feature = "simulator"))]
+mod bme680;
+#[cfg(all(not(feature = "mock"), libm_shims))]
 mod compat;
+#[cfg(feature = "drogue-bme680")]
+mod controller;
+mod driver;
+mod error;
 mod ffi;
+#[cfg(feature = "bsec2")]
+mod heater;
+#[cfg(feature = "mock")]
+mod mock;
+mod sensor;
+#[cfg(feature = "serde")]
+mod serialization;
+mod subscription;
+mod time;
+
+pub mod configuration;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+#[cfg(any(feature = "dump", feature = "std"))]
+pub mod trace;
+
+#[cfg(all(feature = "mock", feature = "bsec2"))]
+compile_error!("The mock backend only implements the BSEC 1.x API, the features `mock` and `bsec2` can't be combined");
+
+#[cfg(feature = "async")]
+pub use asynch::{AsyncBme680, AsyncBme680Error, AsyncBsecDriver};
+#[cfg(any(feature = "async", feature = "simulator"))]
+pub use bme680::Address;
+#[cfg(feature = "drogue-bme680")]
+pub use controller::{Bme680ControllerError, Bme680ControllerSensor};
+pub use driver::{BsecDriver, DriverError, PollResult};
+pub use error::{Error, Warning};
+#[cfg(feature = "bsec2")]
+pub use heater::{HeaterProfile, OperationMode, MAX_HEATER_PROFILE_LEN};
+pub use sensor::{EnvironmentalSensor, Measurement, Oversampling};
+#[cfg(feature = "async")]
+pub use sensor::AsyncEnvironmentalSensor;
+pub use subscription::{RequiredInputs, Subscription};
+pub use time::Timestamp;
 
 pub use ffi::bsec_library_return_t;
+pub use ffi::bsec_physical_sensor_t;
 pub use ffi::bsec_virtual_sensor_t;
 
 use crate::Accuracy::{High, Low, Medium, Unreliable};
+use core::cell::Cell;
+#[cfg(feature = "dump")]
+use core::cell::RefCell;
+#[cfg(feature = "bsec2")]
+use core::cell::UnsafeCell;
 use core::convert::TryInto;
-use drogue_bme680::Oversampling;
+use core::ops::BitOr;
+#[cfg(not(feature = "bsec2"))]
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "drogue-bme680")]
+use drogue_bme680::Data;
 use embedded_time::duration::Milliseconds;
+use error::check;
 use ffi::*;
 
 const BSEC_SAMPLE_RATE_DISABLED: f32 = 65535.0f32;
 const BSEC_SAMPLE_RATE_ULP: f32 = 0.0033333f32;
 const BSEC_SAMPLE_RATE_LP: f32 = 0.33333f32;
 const BSEC_SAMPLE_RATE_ULP_MEASUREMENT_ON_DEMAND: f32 = 0.0f32;
+#[cfg(feature = "bsec2")]
+const BSEC_SAMPLE_RATE_CONT: f32 = 1.0f32;
+#[cfg(feature = "bsec2")]
+const BSEC_SAMPLE_RATE_SCAN: f32 = 0.055556f32;
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum SampleRate {
     Disabled,
     LowPower,
     UltraLowPower,
     OnDemand,
+    /// Continuous mode, one sample every second.
+    #[cfg(feature = "bsec2")]
+    Continuous,
+    /// Scan mode, cycling through the heater profile, used by the gas estimate outputs.
+    #[cfg(feature = "bsec2")]
+    Scan,
 }
 
 impl SampleRate {
@@ -39,8 +106,28 @@ impl SampleRate {
             SampleRate::LowPower => BSEC_SAMPLE_RATE_LP,
             SampleRate::UltraLowPower => BSEC_SAMPLE_RATE_ULP,
             SampleRate::OnDemand => BSEC_SAMPLE_RATE_ULP_MEASUREMENT_ON_DEMAND,
+            #[cfg(feature = "bsec2")]
+            SampleRate::Continuous => BSEC_SAMPLE_RATE_CONT,
+            #[cfg(feature = "bsec2")]
+            SampleRate::Scan => BSEC_SAMPLE_RATE_SCAN,
         }
     }
+
+    pub(crate) fn from_frequency(frequency: f32) -> Option<Self> {
+        [
+            SampleRate::Disabled,
+            SampleRate::LowPower,
+            SampleRate::UltraLowPower,
+            SampleRate::OnDemand,
+            #[cfg(feature = "bsec2")]
+            SampleRate::Continuous,
+            #[cfg(feature = "bsec2")]
+            SampleRate::Scan,
+        ]
+        .iter()
+        .copied()
+        .find(|rate| rate.frequency() == frequency)
+    }
 }
 
 impl From<SampleRate> for f32 {
@@ -55,11 +142,12 @@ impl From<&SampleRate> for f32 {
     }
 }
 
-const NUM_VIRTUAL_SENSORS: u8 = 14;
-const NUM_PHYSICAL_SENSORS: u8 = 8;
+/// Maximum size of a serialized state blob, see [`Bsec::get_state`].
+pub const MAX_STATE_BLOB_SIZE: usize = BSEC_MAX_STATE_BLOB_SIZE as usize;
+/// Maximum size of a serialized configuration, see [`Bsec::set_configuration`].
+pub const MAX_PROPERTY_BLOB_SIZE: usize = BSEC_MAX_PROPERTY_BLOB_SIZE as usize;
+const MAX_WORKBUFFER_SIZE: usize = BSEC_MAX_WORKBUFFER_SIZE as usize;
 
-#[derive(Copy, Clone, Debug)]
-pub struct Error(pub bsec_library_return_t);
 type Result<T> = core::result::Result<T, Error>;
 
 const EMPTY_INPUT: bsec_input_t = bsec_input_t {
@@ -76,28 +164,169 @@ const EMPTY_OUTPUT: bsec_output_t = bsec_output_t {
     accuracy: 0,
 };
 
+/// A set of inputs, which BSEC requests to be processed.
+///
+/// With the `serde` feature, it is serialized as its bits.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct RequestedInputs(u32);
+
+impl RequestedInputs {
+    pub const PRESSURE: Self = Self::of(bsec_physical_sensor_t::BSEC_INPUT_PRESSURE);
+    pub const TEMPERATURE: Self = Self::of(bsec_physical_sensor_t::BSEC_INPUT_TEMPERATURE);
+    pub const HUMIDITY: Self = Self::of(bsec_physical_sensor_t::BSEC_INPUT_HUMIDITY);
+    pub const GAS: Self = Self::of(bsec_physical_sensor_t::BSEC_INPUT_GASRESISTOR);
+    #[cfg(feature = "bsec2")]
+    pub const PROFILE_PART: Self = Self::of(bsec_physical_sensor_t::BSEC_INPUT_PROFILE_PART);
+
+    const fn of(sensor: bsec_physical_sensor_t) -> Self {
+        RequestedInputs(1 << (sensor as u32 - 1))
+    }
+
+    pub const fn empty() -> Self {
+        RequestedInputs(0)
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The requested inputs, which aren't provided by the inputs.
+    pub fn missing(&self, inputs: &Inputs) -> Self {
+        let provided = [
+            (Self::TEMPERATURE, inputs.temperature.is_some()),
+            (Self::HUMIDITY, inputs.humidity.is_some()),
+            (Self::PRESSURE, inputs.pressure.is_some()),
+            (Self::GAS, inputs.gas_resistance.is_some()),
+            #[cfg(feature = "bsec2")]
+            (Self::PROFILE_PART, inputs.profile_part.is_some()),
+        ];
+
+        provided
+            .iter()
+            .filter(|(input, provided)| !provided && self.contains(*input))
+            .fold(Self::empty(), |missing, (input, _)| missing | *input)
+    }
+}
+
+impl BitOr for RequestedInputs {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        RequestedInputs(self.0 | rhs.0)
+    }
+}
+
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Control {
-    pub next_call: Milliseconds,
+    pub next_call: Timestamp,
     pub heater_temperature: u16,
+    /// Serialized as the number of milliseconds.
+    #[cfg_attr(feature = "serde", serde(with = "serialization::milliseconds"))]
     pub heating_duration: Milliseconds,
     pub run_gas: bool,
     pub pressure_oversampling: Oversampling,
     pub temperature_oversampling: Oversampling,
     pub humidity_oversampling: Oversampling,
     pub trigger_measurement: bool,
+    /// The inputs BSEC requests to be processed, after the measurement.
+    pub process_data: RequestedInputs,
+    /// The heater profile to use, in parallel mode.
+    #[cfg(feature = "bsec2")]
+    pub heater_profile: HeaterProfile,
+    /// The operation mode of the sensor.
+    #[cfg(feature = "bsec2")]
+    pub operation_mode: OperationMode,
+    /// A warning reported by the BSEC library, the settings are still valid.
+    pub warning: Option<Warning>,
+}
+
+impl Control {
+    /// Build the inputs from a measurement, only providing the values BSEC requested.
+    #[cfg(feature = "drogue-bme680")]
+    pub fn inputs(&self, data: &Data) -> Inputs {
+        self.measurement_inputs(&data.into())
+    }
+
+    /// Build the inputs from a [`Measurement`], only providing the values BSEC requested.
+    pub fn measurement_inputs(&self, measurement: &Measurement) -> Inputs {
+        let requested = self.process_data;
+
+        Inputs {
+            temperature: if requested.contains(RequestedInputs::TEMPERATURE) {
+                Some(measurement.temperature)
+            } else {
+                None
+            },
+            humidity: if requested.contains(RequestedInputs::HUMIDITY) {
+                Some(measurement.humidity)
+            } else {
+                None
+            },
+            pressure: if requested.contains(RequestedInputs::PRESSURE) {
+                measurement.pressure
+            } else {
+                None
+            },
+            gas_resistance: if requested.contains(RequestedInputs::GAS) && self.run_gas {
+                measurement.gas_resistance
+            } else {
+                None
+            },
+            ..Default::default()
+        }
+    }
+
+    /// The inputs [`Control::inputs`] provides, given a complete measurement.
+    fn expected_inputs(&self) -> RequestedInputs {
+        if self.run_gas {
+            self.process_data
+        } else {
+            RequestedInputs(self.process_data.0 & !RequestedInputs::GAS.0)
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Inputs {
+    /// Temperature, in °C.
     pub temperature: Option<f32>,
+    /// Relative humidity, in %.
     pub humidity: Option<f32>,
+    /// Pressure, in Pa.
     pub pressure: Option<f32>,
+    /// Gas resistance, in Ohm.
     pub gas_resistance: Option<f32>,
+    /// Heat, in °C, the device adds to the sensor. Used for the heat compensated outputs.
+    pub heat_source: Option<f32>,
+    /// Disable (`true`) or re-enable (`false`) the baseline tracker.
+    pub disable_baseline_tracker: Option<bool>,
+    /// The index of the heater profile step of the gas measurement (the gas index of the BME688).
+    #[cfg(feature = "bsec2")]
+    pub profile_part: Option<u8>,
 }
 
-#[derive(Clone, Debug)]
+/// An output of BSEC, with its accuracy.
+///
+/// With the `serde` feature, it is serialized as a struct of the `value` (`None` when
+/// unreliable) and the `accuracy`, see [`Accuracy::accuracy`].
+#[derive(Clone, Debug, Default)]
 pub enum Accuracy<T> {
+    #[default]
     Unreliable,
     Low(T),
     Medium(T),
@@ -111,11 +340,15 @@ impl<T> Accuracy<T> {
             Low(v) | Medium(v) | High(v) => Some(v),
         }
     }
-}
 
-impl<T> Default for Accuracy<T> {
-    fn default() -> Self {
-        Unreliable
+    /// The accuracy, as reported by BSEC: from 0 (unreliable) to 3 (high).
+    pub fn accuracy(&self) -> u8 {
+        match self {
+            Unreliable => 0,
+            Low(_) => 1,
+            Medium(_) => 2,
+            High(_) => 3,
+        }
     }
 }
 
@@ -140,6 +373,7 @@ impl From<&bsec_output_t> for Accuracy<f32> {
 }
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Outputs {
     pub iaq: Option<Accuracy<f32>>,
     pub static_iaq: Option<Accuracy<f32>>,
@@ -153,177 +387,417 @@ pub struct Outputs {
     pub run_in_status: Option<f32>,
     pub sensor_heat_compensated_temperature: Option<f32>,
     pub sensor_heat_compensated_humidity: Option<f32>,
+    /// Not available with BSEC 2.x.
     pub compensated_gas: Option<Accuracy<f32>>,
     pub gas_percentage: Option<Accuracy<f32>>,
+    /// Output of the classifier, trained with BME AI-Studio, for the first gas.
+    #[cfg(feature = "bsec2")]
+    pub gas_estimate_1: Option<Accuracy<f32>>,
+    #[cfg(feature = "bsec2")]
+    pub gas_estimate_2: Option<Accuracy<f32>>,
+    #[cfg(feature = "bsec2")]
+    pub gas_estimate_3: Option<Accuracy<f32>>,
+    #[cfg(feature = "bsec2")]
+    pub gas_estimate_4: Option<Accuracy<f32>>,
+    /// The index of the heater profile step of the raw gas resistance.
+    #[cfg(feature = "bsec2")]
+    pub raw_gas_index: Option<f32>,
+    /// The inputs requested by the last call to [`Bsec::sensor_control`], which weren't
+    /// provided. BSEC processes the data anyway, but the outputs may be degraded.
+    pub missing_inputs: RequestedInputs,
+    /// A warning reported by the BSEC library, the outputs are still valid.
+    pub warning: Option<Warning>,
 }
 
-macro_rules! sensors {
-    ($id:ident, $($next:ident),+) => {
-        [ sensors!($id), $(sensors!($next)),* ]
-    };
-    ($id:ident) => {
-        bsec_sensor_configuration_t {
-            sensor_id: crate::bsec_virtual_sensor_t::$id as u8,
-            sample_rate: BSEC_SAMPLE_RATE_DISABLED,
-        }
-    };
+/// Flag tracking if there is a live instance of [`Bsec`].
+#[cfg(not(feature = "bsec2"))]
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Mark the instance as taken, returning the previous state.
+#[cfg(all(not(feature = "bsec2"), target_has_atomic = "8"))]
+fn take() -> bool {
+    TAKEN.swap(true, Ordering::AcqRel)
+}
+
+/// Mark the instance as taken, returning the previous state.
+///
+/// The target has no atomic compare-and-swap, so this isn't safe to use from interrupts.
+#[cfg(all(not(feature = "bsec2"), not(target_has_atomic = "8")))]
+fn take() -> bool {
+    let taken = TAKEN.load(Ordering::Acquire);
+    TAKEN.store(true, Ordering::Release);
+    taken
+}
+
+/// Size of the memory of a BSEC 2.x instance.
+#[cfg(feature = "bsec2")]
+const INSTANCE_SIZE: usize = BSEC_INSTANCE_SIZE as usize;
+
+/// Memory holding the state of a BSEC 2.x instance.
+///
+/// The library keeps pointers into this memory, so it must not move once the instance is
+/// initialized. Therefore, it is provided by the caller, e.g. as a `static`, and is borrowed by
+/// the [`Bsec`] for its whole lifetime:
+///
+/// ```ignore
+/// let memory = cortex_m::singleton!(: InstanceMemory = InstanceMemory::new()).unwrap();
+/// let bsec = Bsec::new(memory)?.unwrap();
+/// ```
+#[cfg(feature = "bsec2")]
+#[repr(C, align(8))]
+pub struct InstanceMemory(UnsafeCell<[u8; INSTANCE_SIZE]>);
+
+#[cfg(feature = "bsec2")]
+impl InstanceMemory {
+    pub const fn new() -> Self {
+        InstanceMemory(UnsafeCell::new([0u8; INSTANCE_SIZE]))
+    }
+}
+
+#[cfg(feature = "bsec2")]
+impl Default for InstanceMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Call a function of the BSEC library.
+///
+/// With BSEC 2.x, this calls the instance variant (`_m`) of the function, using the memory of
+/// the instance. Otherwise, the global variant is called.
+macro_rules! bsec_call {
+    ($self:ident, $instance:ident, $global:ident ( $($arg:expr),* $(,)? )) => {{
+        #[cfg(feature = "bsec2")]
+        let result = $instance($self.instance(), $($arg),*);
+        #[cfg(not(feature = "bsec2"))]
+        let result = $global($($arg),*);
+        result
+    }};
 }
 
+/// Access to the BSEC library.
+///
+/// The BSEC 1.x library keeps its state in global variables, so there may only be a single
+/// instance alive at a time.
+///
+/// With BSEC 2.x (the `bsec2` feature), each instance keeps the library's state in its own
+/// [`InstanceMemory`], so multiple instances, each processing a different sensor, may coexist.
+/// Each has its own subscription, state and schedule.
 pub struct Bsec {
-    virtual_sensors: [bsec_sensor_configuration_t; NUM_VIRTUAL_SENSORS as usize],
-    internal_physical_sensors: [bsec_sensor_configuration_t; NUM_PHYSICAL_SENSORS as usize],
-    num_physical_sensors: usize,
+    #[cfg(feature = "bsec2")]
+    instance: &'static mut InstanceMemory,
+    subscription: Subscription,
+    required_inputs: RequiredInputs,
+    /// The inputs requested by the last call to `sensor_control`, checked by the next call to
+    /// `process_data`.
+    expected_inputs: Cell<Option<RequestedInputs>>,
+    #[cfg(feature = "dump")]
+    tracer: RefCell<Option<trace::Tracer>>,
+    /// If there were calls to the library, which would have been traced.
+    #[cfg(feature = "dump")]
+    traced: Cell<bool>,
 }
 
 impl Bsec {
+    /// Create a new instance.
+    ///
+    /// Returns `None` if there already is a live instance.
+    #[cfg(not(feature = "bsec2"))]
     pub fn new() -> Result<Option<Self>> {
-        Some(
-            Bsec {
-                virtual_sensors: sensors![
-                    BSEC_OUTPUT_IAQ,
-                    BSEC_OUTPUT_STATIC_IAQ,
-                    BSEC_OUTPUT_CO2_EQUIVALENT,
-                    BSEC_OUTPUT_BREATH_VOC_EQUIVALENT,
-                    BSEC_OUTPUT_RAW_TEMPERATURE,
-                    BSEC_OUTPUT_RAW_PRESSURE,
-                    BSEC_OUTPUT_RAW_HUMIDITY,
-                    BSEC_OUTPUT_RAW_GAS,
-                    BSEC_OUTPUT_STABILIZATION_STATUS,
-                    BSEC_OUTPUT_RUN_IN_STATUS,
-                    BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_TEMPERATURE,
-                    BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_HUMIDITY,
-                    BSEC_OUTPUT_COMPENSATED_GAS,
-                    BSEC_OUTPUT_GAS_PERCENTAGE
-                ],
-                internal_physical_sensors: [bsec_sensor_configuration_t {
-                    sensor_id: 0,
-                    sample_rate: BSEC_SAMPLE_RATE_DISABLED,
-                }; NUM_PHYSICAL_SENSORS as usize],
-                num_physical_sensors: 0,
-            }
-            .init(),
-        )
-        .transpose()
+        if take() {
+            return Ok(None);
+        }
+
+        unsafe { Self::steal() }.map(Some)
+    }
+
+    /// Create a new instance, keeping the state of the library in the provided memory.
+    ///
+    /// With BSEC 2.x, there is no limit on the number of instances, so this always returns a
+    /// new instance.
+    #[cfg(feature = "bsec2")]
+    pub fn new(instance: &'static mut InstanceMemory) -> Result<Option<Self>> {
+        Self::from_parts(instance).init().map(Some)
+    }
+
+    /// Create a new instance, without checking for an existing one.
+    ///
+    /// # Safety
+    ///
+    /// The BSEC 1.x library keeps its state in global variables, which are shared by all
+    /// instances. The caller must ensure that there is no other live instance, e.g. one which
+    /// was leaked using [`core::mem::forget`], as the instances would corrupt each other's state.
+    ///
+    /// With BSEC 2.x, use [`Bsec::new`] instead, as each instance has its own memory.
+    #[cfg(not(feature = "bsec2"))]
+    pub unsafe fn steal() -> Result<Self> {
+        TAKEN.store(true, Ordering::Release);
+
+        Self::from_parts().init()
+    }
+
+    fn from_parts(#[cfg(feature = "bsec2")] instance: &'static mut InstanceMemory) -> Self {
+        Bsec {
+            #[cfg(feature = "bsec2")]
+            instance,
+            subscription: Subscription::new(),
+            required_inputs: RequiredInputs::default(),
+            expected_inputs: Cell::new(None),
+            #[cfg(feature = "dump")]
+            tracer: RefCell::new(None),
+            #[cfg(feature = "dump")]
+            traced: Cell::new(false),
+        }
+    }
+
+    /// Create a new instance, and apply the provided configuration.
+    ///
+    /// See [`configuration`] for the configurations shipped with the BSEC library.
+    #[cfg(not(feature = "bsec2"))]
+    pub fn with_configuration(configuration: &[u8]) -> Result<Option<Self>> {
+        Self::new()?
+            .map(|bsec| bsec.configured(configuration))
+            .transpose()
+    }
+
+    /// Create a new instance, keeping the state of the library in the provided memory, and apply
+    /// the provided configuration.
+    ///
+    /// See [`configuration`] for the configurations shipped with the BSEC library.
+    #[cfg(feature = "bsec2")]
+    pub fn with_configuration(
+        instance: &'static mut InstanceMemory,
+        configuration: &[u8],
+    ) -> Result<Option<Self>> {
+        Self::new(instance)?
+            .map(|bsec| bsec.configured(configuration))
+            .transpose()
+    }
+
+    fn configured(mut self, configuration: &[u8]) -> Result<Self> {
+        self.set_configuration(configuration)?;
+        Ok(self)
     }
 
     fn init(self) -> Result<Self> {
-        match unsafe { bsec_init() } {
-            bsec_library_return_t::BSEC_OK => Ok(self),
-            e => Err(Error(e)),
+        #[cfg(feature = "bsec2")]
+        assert!(
+            unsafe { bsec_get_instance_size_m() } <= INSTANCE_SIZE,
+            "Instance size of the BSEC library exceeds the size of the bindings"
+        );
+
+        if let Some(warning) = check(
+            unsafe { bsec_call!(self, bsec_init_m, bsec_init()) },
+            Error::Init,
+        )? {
+            log::warn!("bsec_init: {}", warning);
+        }
+        Ok(self)
+    }
+
+    /// Pointer to the memory of the instance, handed to the BSEC 2.x library.
+    #[cfg(feature = "bsec2")]
+    fn instance(&self) -> *mut cty::c_void {
+        self.instance.0.get() as *mut cty::c_void
+    }
+
+    /// Destroy the instance, returning its memory, e.g. to create a new instance in it.
+    ///
+    /// A new instance initializes the memory again, so none of the state is kept.
+    #[cfg(feature = "bsec2")]
+    pub fn into_memory(self) -> &'static mut InstanceMemory {
+        self.instance
+    }
+
+    /// Record all following calls to the BSEC library into the sink, starting with the header of
+    /// the trace. See [`trace`] for the format.
+    ///
+    /// A previously set sink is returned.
+    #[cfg(feature = "dump")]
+    pub fn set_trace_sink(
+        &mut self,
+        sink: &'static mut (dyn trace::TraceSink + Send),
+    ) -> Option<&'static mut (dyn trace::TraceSink + Send)> {
+        let tracer = trace::Tracer::new(sink, version(), !self.traced.get());
+        self.tracer
+            .replace(Some(tracer))
+            .map(trace::Tracer::into_sink)
+    }
+
+    /// Stop recording the calls, returning the sink.
+    #[cfg(feature = "dump")]
+    pub fn take_trace_sink(&mut self) -> Option<&'static mut (dyn trace::TraceSink + Send)> {
+        self.tracer.take().map(trace::Tracer::into_sink)
+    }
+
+    #[cfg(feature = "dump")]
+    fn trace<F: FnOnce(&mut trace::Tracer)>(&self, f: F) {
+        self.traced.set(true);
+        if let Some(tracer) = self.tracer.borrow_mut().as_mut() {
+            f(tracer);
         }
     }
 
-    #[allow(dead_code)]
-    fn physical_sensors(&self) -> &[bsec_sensor_configuration_t] {
-        &self.internal_physical_sensors[0..self.num_physical_sensors]
+    /// The physical inputs required by the current subscription.
+    pub fn required_inputs(&self) -> &RequiredInputs {
+        &self.required_inputs
     }
 
+    /// Update the sample rate of the provided sensors, keeping all others.
     pub fn update_subscription(
         &mut self,
         sample_rate: SampleRate,
         sensors: &[bsec_virtual_sensor_t],
-    ) -> Result<()> {
-        self.do_set_subscription(false, sample_rate, sensors)
+    ) -> Result<RequiredInputs> {
+        let subscription = self.subscription.clone().with_all(sensors, sample_rate);
+        self.subscribe(&subscription)
     }
 
+    /// Subscribe to the provided sensors, disabling all others.
     pub fn set_subscription(
         &mut self,
         sample_rate: SampleRate,
         sensors: &[bsec_virtual_sensor_t],
-    ) -> Result<()> {
-        self.do_set_subscription(true, sample_rate, sensors)
+    ) -> Result<RequiredInputs> {
+        self.subscribe(&Subscription::new().with_all(sensors, sample_rate))
     }
 
-    fn do_set_subscription(
-        &mut self,
-        disable_others: bool,
-        sample_rate: SampleRate,
-        sensors: &[bsec_virtual_sensor_t],
-    ) -> Result<()> {
-        if disable_others {
-            // reset all sensors
-            for sensor in &mut self.virtual_sensors {
-                sensor.sample_rate = BSEC_SAMPLE_RATE_DISABLED;
-            }
-        }
+    /// Apply a subscription, replacing the current one.
+    ///
+    /// All sample rates are applied in a single call to the BSEC library. On success, the
+    /// physical inputs required by the subscription are returned.
+    pub fn subscribe(&mut self, subscription: &Subscription) -> Result<RequiredInputs> {
+        subscription.validate()?;
 
-        // set new sensors
-        for update in sensors {
-            for sensor in &mut self.virtual_sensors {
-                if sensor.sensor_id == *update as u8 {
-                    sensor.sample_rate = sample_rate.frequency();
-                }
-            }
-        }
+        log::info!("Subscription: {}", subscription);
 
-        log::info!("Subscription: {:?}", self.virtual_sensors);
+        let mut required_sensors = [bsec_sensor_configuration_t {
+            sensor_id: 0,
+            sample_rate: BSEC_SAMPLE_RATE_DISABLED,
+        }; BSEC_MAX_PHYSICAL_SENSOR as usize];
+        let mut required_sensors_len = BSEC_MAX_PHYSICAL_SENSOR as u8;
+        let result = unsafe {
+            bsec_call!(
+                self,
+                bsec_update_subscription_m,
+                bsec_update_subscription(
+                    subscription.sensors.as_ptr(),
+                    NUM_VIRTUAL_SENSORS,
+                    required_sensors.as_mut_ptr(),
+                    &mut required_sensors_len,
+                )
+            )
+        };
 
-        let mut required_sensors_len = NUM_PHYSICAL_SENSORS;
-        match unsafe {
-            bsec_update_subscription(
-                self.virtual_sensors.as_ptr(),
-                NUM_VIRTUAL_SENSORS,
-                self.internal_physical_sensors.as_mut_ptr(),
-                &mut required_sensors_len,
+        #[cfg(feature = "dump")]
+        self.trace(|tracer| {
+            tracer.update_subscription(
+                result,
+                &subscription.sensors,
+                &required_sensors[..(required_sensors_len as usize).min(required_sensors.len())],
             )
-        } {
-            bsec_library_return_t::BSEC_OK => {
-                assert!(required_sensors_len <= NUM_PHYSICAL_SENSORS);
-                self.num_physical_sensors = required_sensors_len as usize;
-                Ok(())
-            }
-            e => Err(Error(e)),
+        });
+
+        if let Some(warning) = check(result, Error::UpdateSubscription)? {
+            log::warn!("bsec_update_subscription: {}", warning);
         }
+
+        assert!(required_sensors_len as usize <= required_sensors.len());
+        self.required_inputs =
+            RequiredInputs::from_sensors(&required_sensors[..required_sensors_len as usize]);
+        self.subscription = subscription.clone();
+
+        log::info!("Required inputs: {}", self.required_inputs);
+
+        Ok(self.required_inputs)
     }
 
-    pub fn sensor_control(&self, now: Milliseconds) -> Result<Control> {
-        let now = (now.0 as i64) * 1_000_000;
+    /// The currently active subscription.
+    pub fn subscription(&self) -> &Subscription {
+        &self.subscription
+    }
 
-        let mut settings = bsec_bme_settings_t {
-            next_call: 0,
-            process_data: 0,
-            heater_temperature: 0,
-            heating_duration: 0,
-            run_gas: 0,
-            pressure_oversampling: 0,
-            temperature_oversampling: 0,
-            humidity_oversampling: 0,
-            trigger_measurement: 0,
-        };
+    /// Get the sensor settings for the current point in time.
+    ///
+    /// Thin adapter over [`Bsec::sensor_control`], the timestamp wraps after ~49.7 days.
+    pub fn sensor_control_ms(&self, now: Milliseconds) -> Result<Control> {
+        self.sensor_control(now.into())
+    }
 
-        let result = unsafe { bsec_sensor_control(now, &mut settings) };
+    /// Get the sensor settings for the current point in time.
+    pub fn sensor_control(&self, now: Timestamp) -> Result<Control> {
+        let mut settings = bsec_bme_settings_t::default();
+
+        let result = unsafe {
+            bsec_call!(
+                self,
+                bsec_sensor_control_m,
+                bsec_sensor_control(now.as_nanos(), &mut settings)
+            )
+        };
 
         log::info!("bsec_sensor_control -> {:?} = {:?}", result, settings);
 
-        match result {
-            bsec_library_return_t::BSEC_OK => Ok(Control {
-                next_call: Milliseconds((settings.next_call / 1_000_000) as u32),
-                heater_temperature: settings.heater_temperature,
-                heating_duration: Milliseconds(settings.heating_duration as u32),
-                run_gas: settings.run_gas != 0,
-                pressure_oversampling: settings.pressure_oversampling.into(),
-                temperature_oversampling: settings.temperature_oversampling.into(),
-                humidity_oversampling: settings.humidity_oversampling.into(),
-                trigger_measurement: settings.trigger_measurement != 0,
-            }),
-            e => Err(Error(e)),
-        }
+        #[cfg(feature = "dump")]
+        self.trace(|tracer| tracer.sensor_control(now.as_nanos(), result, &settings));
+
+        let warning = check(result, Error::SensorControl)?;
+
+        let control = Control {
+            next_call: Timestamp::from_nanos(settings.next_call),
+            heater_temperature: settings.heater_temperature,
+            #[cfg(not(feature = "bsec2"))]
+            heating_duration: Milliseconds(settings.heating_duration as u32),
+            #[cfg(feature = "bsec2")]
+            heating_duration: Milliseconds(settings.heater_duration as u32),
+            run_gas: settings.run_gas != 0,
+            pressure_oversampling: settings.pressure_oversampling.into(),
+            temperature_oversampling: settings.temperature_oversampling.into(),
+            humidity_oversampling: settings.humidity_oversampling.into(),
+            trigger_measurement: settings.trigger_measurement != 0,
+            process_data: RequestedInputs(settings.process_data),
+            #[cfg(feature = "bsec2")]
+            heater_profile: HeaterProfile::from_settings(&settings),
+            #[cfg(feature = "bsec2")]
+            operation_mode: OperationMode::from_raw(settings.op_mode),
+            warning,
+        };
+        self.expected_inputs.set(Some(control.expected_inputs()));
+
+        Ok(control)
     }
 
-    pub fn process_data(
+    /// Process the measured data.
+    ///
+    /// Thin adapter over [`Bsec::process_data`], the timestamp wraps after ~49.7 days.
+    pub fn process_data_ms(
         &mut self,
         timestamp: Milliseconds,
         input_data: &Inputs,
     ) -> Result<Outputs> {
+        self.process_data(timestamp.into(), input_data)
+    }
+
+    /// Process the measured data.
+    pub fn process_data(&mut self, timestamp: Timestamp, input_data: &Inputs) -> Result<Outputs> {
+        let missing_inputs = self
+            .expected_inputs
+            .take()
+            .map(|expected| expected.missing(input_data))
+            .unwrap_or_default();
+        if !missing_inputs.is_empty() {
+            log::warn!("Missing requested inputs: {:#x}", missing_inputs.bits());
+        }
+
         let mut input = [EMPTY_INPUT; NUM_PHYSICAL_SENSORS as usize];
 
-        let timestamp = (timestamp.0 as i64) * 1_000_000;
+        let timestamp = timestamp.as_nanos();
 
         let mut idx = 0u8;
         if let Some(temperature) = input_data.temperature {
             input[idx as usize] = bsec_input_t {
                 sensor_id: bsec_physical_sensor_t::BSEC_INPUT_TEMPERATURE as u8,
-                signal: temperature / 100.0,
+                signal: temperature,
                 time_stamp: timestamp,
                 signal_dimensions: 0,
             };
@@ -332,7 +806,7 @@ impl Bsec {
         if let Some(humidity) = input_data.humidity {
             input[idx as usize] = bsec_input_t {
                 sensor_id: bsec_physical_sensor_t::BSEC_INPUT_HUMIDITY as u8,
-                signal: humidity / 100.0,
+                signal: humidity,
                 time_stamp: timestamp,
                 signal_dimensions: 0,
             };
@@ -356,6 +830,34 @@ impl Bsec {
             };
             idx += 1;
         }
+        if let Some(heat_source) = input_data.heat_source {
+            input[idx as usize] = bsec_input_t {
+                sensor_id: bsec_physical_sensor_t::BSEC_INPUT_HEATSOURCE as u8,
+                signal: heat_source,
+                time_stamp: timestamp,
+                signal_dimensions: 0,
+            };
+            idx += 1;
+        }
+        if let Some(disable_baseline_tracker) = input_data.disable_baseline_tracker {
+            input[idx as usize] = bsec_input_t {
+                sensor_id: bsec_physical_sensor_t::BSEC_INPUT_DISABLE_BASELINE_TRACKER as u8,
+                signal: if disable_baseline_tracker { 1.0 } else { 0.0 },
+                time_stamp: timestamp,
+                signal_dimensions: 0,
+            };
+            idx += 1;
+        }
+        #[cfg(feature = "bsec2")]
+        if let Some(profile_part) = input_data.profile_part {
+            input[idx as usize] = bsec_input_t {
+                sensor_id: bsec_physical_sensor_t::BSEC_INPUT_PROFILE_PART as u8,
+                signal: profile_part as f32,
+                time_stamp: timestamp,
+                signal_dimensions: 0,
+            };
+            idx += 1;
+        }
 
         let mut output = [EMPTY_OUTPUT; NUM_VIRTUAL_SENSORS as usize];
         let mut num_outputs = NUM_VIRTUAL_SENSORS;
@@ -366,17 +868,37 @@ impl Bsec {
             num_outputs
         );
 
-        let result =
-            unsafe { bsec_do_steps(input.as_ptr(), idx, output.as_mut_ptr(), &mut num_outputs) };
+        let result = unsafe {
+            bsec_call!(
+                self,
+                bsec_do_steps_m,
+                bsec_do_steps(input.as_ptr(), idx, output.as_mut_ptr(), &mut num_outputs)
+            )
+        };
 
         log::info!("do_steps = {:?}", result);
 
+        #[cfg(feature = "dump")]
+        self.trace(|tracer| {
+            tracer.do_steps(
+                result,
+                &input[..idx as usize],
+                &output[..(num_outputs as usize).min(output.len())],
+            )
+        });
+
+        let warning = check(result, Error::DoSteps)?;
+
         assert!(num_outputs <= NUM_VIRTUAL_SENSORS);
 
         log::info!("Returned outputs: {}", num_outputs);
         let output = &output[..num_outputs as usize];
 
-        let mut outputs = Outputs::default();
+        let mut outputs = Outputs {
+            missing_inputs,
+            warning,
+            ..Default::default()
+        };
 
         for out in output {
             log::info!("Output: {:?}", out);
@@ -416,12 +938,33 @@ impl Bsec {
                 Ok(bsec_virtual_sensor_t::BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_HUMIDITY) => {
                     outputs.sensor_heat_compensated_humidity = Some(out.signal)
                 }
+                #[cfg(not(feature = "bsec2"))]
                 Ok(bsec_virtual_sensor_t::BSEC_OUTPUT_COMPENSATED_GAS) => {
                     outputs.compensated_gas = Some(out.into())
                 }
                 Ok(bsec_virtual_sensor_t::BSEC_OUTPUT_GAS_PERCENTAGE) => {
                     outputs.gas_percentage = Some(out.into())
                 }
+                #[cfg(feature = "bsec2")]
+                Ok(bsec_virtual_sensor_t::BSEC_OUTPUT_GAS_ESTIMATE_1) => {
+                    outputs.gas_estimate_1 = Some(out.into())
+                }
+                #[cfg(feature = "bsec2")]
+                Ok(bsec_virtual_sensor_t::BSEC_OUTPUT_GAS_ESTIMATE_2) => {
+                    outputs.gas_estimate_2 = Some(out.into())
+                }
+                #[cfg(feature = "bsec2")]
+                Ok(bsec_virtual_sensor_t::BSEC_OUTPUT_GAS_ESTIMATE_3) => {
+                    outputs.gas_estimate_3 = Some(out.into())
+                }
+                #[cfg(feature = "bsec2")]
+                Ok(bsec_virtual_sensor_t::BSEC_OUTPUT_GAS_ESTIMATE_4) => {
+                    outputs.gas_estimate_4 = Some(out.into())
+                }
+                #[cfg(feature = "bsec2")]
+                Ok(bsec_virtual_sensor_t::BSEC_OUTPUT_RAW_GAS_INDEX) => {
+                    outputs.raw_gas_index = Some(out.signal)
+                }
                 _ => {
                     // Unknown output, we ignore it
                 }
@@ -430,9 +973,108 @@ impl Bsec {
 
         Ok(outputs)
     }
+
+    /// Apply a serialized configuration.
+    ///
+    /// This should be called before subscribing to any outputs.
+    pub fn set_configuration(&mut self, configuration: &[u8]) -> Result<()> {
+        let mut work_buffer = [0u8; MAX_WORKBUFFER_SIZE];
+
+        let result = unsafe {
+            bsec_call!(
+                self,
+                bsec_set_configuration_m,
+                bsec_set_configuration(
+                    configuration.as_ptr(),
+                    configuration.len() as u32,
+                    work_buffer.as_mut_ptr(),
+                    work_buffer.len() as u32,
+                )
+            )
+        };
+
+        log::info!("bsec_set_configuration -> {:?}", result);
+
+        #[cfg(feature = "dump")]
+        self.trace(|tracer| tracer.set_configuration(result, configuration));
+
+        if let Some(warning) = check(result, Error::SetConfiguration)? {
+            log::warn!("bsec_set_configuration: {}", warning);
+        }
+        Ok(())
+    }
+
+    /// Serialize the current internal state into the provided buffer.
+    ///
+    /// The buffer should be at least [`MAX_STATE_BLOB_SIZE`] bytes long. On success, the part
+    /// of the buffer holding the state is returned.
+    pub fn get_state<'b>(&self, buffer: &'b mut [u8]) -> Result<&'b [u8]> {
+        let mut work_buffer = [0u8; MAX_WORKBUFFER_SIZE];
+        let mut len = 0u32;
+
+        let result = unsafe {
+            bsec_call!(
+                self,
+                bsec_get_state_m,
+                bsec_get_state(
+                    0,
+                    buffer.as_mut_ptr(),
+                    buffer.len() as u32,
+                    work_buffer.as_mut_ptr(),
+                    work_buffer.len() as u32,
+                    &mut len,
+                )
+            )
+        };
+
+        log::info!("bsec_get_state -> {:?} = {} bytes", result, len);
+
+        if let Some(warning) = check(result, Error::GetState)? {
+            log::warn!("bsec_get_state: {}", warning);
+        }
+
+        assert!(len as usize <= buffer.len());
+        Ok(&buffer[..len as usize])
+    }
+
+    /// Restore the internal state from a blob previously returned by [`Bsec::get_state`].
+    pub fn set_state(&mut self, state: &[u8]) -> Result<()> {
+        let mut work_buffer = [0u8; MAX_WORKBUFFER_SIZE];
+
+        let result = unsafe {
+            bsec_call!(
+                self,
+                bsec_set_state_m,
+                bsec_set_state(
+                    state.as_ptr(),
+                    state.len() as u32,
+                    work_buffer.as_mut_ptr(),
+                    work_buffer.len() as u32,
+                )
+            )
+        };
+
+        log::info!("bsec_set_state -> {:?}", result);
+
+        #[cfg(feature = "dump")]
+        self.trace(|tracer| tracer.set_state(result, state));
+
+        if let Some(warning) = check(result, Error::SetState)? {
+            log::warn!("bsec_set_state: {}", warning);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "bsec2"))]
+impl Drop for Bsec {
+    fn drop(&mut self) {
+        TAKEN.store(false, Ordering::Release);
+    }
 }
 
 #[derive(Copy, Clone, Debug, Eq, Ord, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Version {
     pub major: u8,
     pub minor: u8,
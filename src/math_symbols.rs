@@ -0,0 +1,10 @@
+// Included by the build script and the tests, checking the libm shims of `compat.rs`.
+
+/// The C math functions, which might be referenced by the library.
+const MATH_SYMBOLS: &[&str] = &[
+    "acos", "acosf", "asin", "asinf", "atan", "atan2", "atan2f", "atanf", "cbrt", "cbrtf", "ceil", "ceilf", "cos",
+    "cosf", "cosh", "coshf", "exp", "exp2", "exp2f", "expf", "expm1", "expm1f", "fabs", "fabsf", "floor", "floorf",
+    "fmax", "fmaxf", "fmin", "fminf", "fmod", "fmodf", "hypot", "hypotf", "ldexp", "ldexpf", "log", "log10",
+    "log10f", "log1p", "log1pf", "log2", "log2f", "logf", "pow", "powf", "round", "roundf", "sin", "sinf", "sinh",
+    "sinhf", "sqrt", "sqrtf", "tan", "tanf", "tanh", "tanhf", "trunc", "truncf",
+];
@@ -0,0 +1,547 @@
+//! A pure Rust, deterministic stand-in for the BSEC library.
+//!
+//! It implements the functions of the bindings which are used by this crate, using the types of
+//! the pre-generated BSEC 1.4.8.0 bindings, so that the crate can be built and exercised on a
+//! host, without linking `libalgobsec`. The outputs are
+//! only plausible, they are not meant to match the real algorithm.
+
+use core::cell::UnsafeCell;
+
+use crate::ffi::{
+    bsec_bme_settings_t, bsec_input_t, bsec_library_return_t, bsec_output_t,
+    bsec_physical_sensor_t, bsec_sensor_configuration_t, bsec_version_t, bsec_virtual_sensor_t,
+    BSEC_MAX_PHYSICAL_SENSOR, BSEC_MAX_PROPERTY_BLOB_SIZE, BSEC_MAX_WORKBUFFER_SIZE,
+};
+
+use bsec_library_return_t::*;
+use bsec_physical_sensor_t::*;
+use bsec_virtual_sensor_t::*;
+
+const SAMPLE_RATE_DISABLED: f32 = 65535.0;
+const SAMPLE_RATE_ULP: f32 = 0.0033333;
+const SAMPLE_RATE_LP: f32 = 0.33333;
+const SAMPLE_RATE_ON_DEMAND: f32 = 0.0;
+
+const HEATER_TEMPERATURE: u16 = 320;
+const HEATING_DURATION: u16 = 197;
+
+/// Number of gas samples until the sensor is reported as stabilized.
+const STABILIZATION_SAMPLES: u32 = 10;
+/// Number of gas samples until the run-in is reported as finished.
+const RUN_IN_SAMPLES: u32 = 50;
+/// Number of gas samples until the accuracy is reported as high.
+const CALIBRATION_SAMPLES: u32 = 200;
+
+const STATE_MAGIC: [u8; 4] = *b"MBSC";
+const STATE_SIZE: usize = 4 + 4 + 4 + 4 + 1;
+
+/// Virtual sensors derived from the gas sensor.
+const GAS_SENSORS: &[bsec_virtual_sensor_t] = &[
+    BSEC_OUTPUT_IAQ,
+    BSEC_OUTPUT_STATIC_IAQ,
+    BSEC_OUTPUT_CO2_EQUIVALENT,
+    BSEC_OUTPUT_BREATH_VOC_EQUIVALENT,
+    BSEC_OUTPUT_RAW_GAS,
+    BSEC_OUTPUT_STABILIZATION_STATUS,
+    BSEC_OUTPUT_RUN_IN_STATUS,
+    BSEC_OUTPUT_COMPENSATED_GAS,
+    BSEC_OUTPUT_GAS_PERCENTAGE,
+];
+
+const NUM_SENSORS: usize = crate::ffi::NUM_VIRTUAL_SENSORS as usize;
+
+struct State {
+    /// Sample rate per virtual sensor, indexed by the position in [`bsec_virtual_sensor_t::ALL`](crate::bsec_virtual_sensor_t::ALL).
+    rates: [f32; NUM_SENSORS],
+    /// Sample rate of the measurements.
+    rate: f32,
+    next_call: i64,
+    samples: u32,
+    baseline: f32,
+    baseline_tracker_disabled: bool,
+}
+
+impl State {
+    const fn new() -> Self {
+        State {
+            rates: [SAMPLE_RATE_DISABLED; NUM_SENSORS],
+            rate: SAMPLE_RATE_DISABLED,
+            next_call: 0,
+            samples: 0,
+            baseline: 0.0,
+            baseline_tracker_disabled: false,
+        }
+    }
+
+    fn rate_of(&self, sensor: bsec_virtual_sensor_t) -> f32 {
+        bsec_virtual_sensor_t::ALL
+            .iter()
+            .position(|s| *s == sensor)
+            .map(|i| self.rates[i])
+            .unwrap_or(SAMPLE_RATE_DISABLED)
+    }
+
+    fn enabled(&self, sensor: bsec_virtual_sensor_t) -> bool {
+        self.rate_of(sensor) != SAMPLE_RATE_DISABLED
+    }
+
+    fn gas_enabled(&self) -> bool {
+        GAS_SENSORS.iter().any(|s| self.enabled(*s))
+    }
+
+    fn period(&self) -> Option<i64> {
+        if self.rate == SAMPLE_RATE_DISABLED || self.rate == SAMPLE_RATE_ON_DEMAND {
+            None
+        } else {
+            Some(libm::roundf(1.0 / self.rate) as i64 * 1_000_000_000)
+        }
+    }
+
+    fn accuracy(&self) -> u8 {
+        match self.samples {
+            s if s < STABILIZATION_SAMPLES => 0,
+            s if s < RUN_IN_SAMPLES => 1,
+            s if s < CALIBRATION_SAMPLES => 2,
+            _ => 3,
+        }
+    }
+}
+
+struct Global(UnsafeCell<State>);
+
+unsafe impl Sync for Global {}
+
+static STATE: Global = Global(UnsafeCell::new(State::new()));
+
+/// Access the global state, just like the real library does.
+unsafe fn state() -> &'static mut State {
+    &mut *STATE.0.get()
+}
+
+fn is_known_rate(rate: f32) -> bool {
+    rate == SAMPLE_RATE_DISABLED
+        || rate == SAMPLE_RATE_ULP
+        || rate == SAMPLE_RATE_LP
+        || rate == SAMPLE_RATE_ON_DEMAND
+}
+
+fn virtual_sensor(id: u8) -> Option<bsec_virtual_sensor_t> {
+    bsec_virtual_sensor_t::ALL.iter().copied().find(|s| *s as u8 == id)
+}
+
+pub unsafe fn bsec_init() -> bsec_library_return_t {
+    *state() = State::new();
+    BSEC_OK
+}
+
+pub unsafe fn bsec_get_version(bsec_version_p: *mut bsec_version_t) -> bsec_library_return_t {
+    *bsec_version_p = bsec_version_t {
+        major: 1,
+        minor: 4,
+        major_bugfix: 8,
+        minor_bugfix: 0,
+    };
+    BSEC_OK
+}
+
+pub unsafe fn bsec_update_subscription(
+    requested_virtual_sensors: *const bsec_sensor_configuration_t,
+    n_requested_virtual_sensors: u8,
+    required_sensor_settings: *mut bsec_sensor_configuration_t,
+    n_required_sensor_settings: *mut u8,
+) -> bsec_library_return_t {
+    let requested =
+        core::slice::from_raw_parts(requested_virtual_sensors, n_requested_virtual_sensors as usize);
+
+    let mut rates = state().rates;
+    let mut warning = None;
+
+    for (i, request) in requested.iter().enumerate() {
+        if requested[..i].iter().any(|r| r.sensor_id == request.sensor_id) {
+            return BSEC_E_SU_DUPLICATEGATE;
+        }
+        if !is_known_rate(request.sample_rate) {
+            return BSEC_E_SU_SAMPLERATELIMITS;
+        }
+        match virtual_sensor(request.sensor_id)
+            .and_then(|sensor| bsec_virtual_sensor_t::ALL.iter().position(|s| *s == sensor))
+        {
+            Some(idx) => rates[idx] = request.sample_rate,
+            None => warning = Some(BSEC_W_SU_UNKNOWNOUTPUTGATE),
+        }
+    }
+
+    // all gas based sensors must share the same rate, on demand measurements aside
+
+    let mut gas_rate = None;
+    for sensor in GAS_SENSORS {
+        let idx = bsec_virtual_sensor_t::ALL.iter().position(|s| s == sensor).unwrap();
+        let rate = rates[idx];
+        if rate == SAMPLE_RATE_DISABLED || rate == SAMPLE_RATE_ON_DEMAND {
+            continue;
+        }
+        match gas_rate {
+            None => gas_rate = Some(rate),
+            Some(gas_rate) if rate == gas_rate => {}
+            Some(_) => return BSEC_E_SU_MULTGASSAMPLINTVL,
+        }
+    }
+
+    // the measurement runs at the highest requested rate
+
+    let rate = rates
+        .iter()
+        .copied()
+        .filter(|r| *r != SAMPLE_RATE_DISABLED && *r != SAMPLE_RATE_ON_DEMAND)
+        .fold(SAMPLE_RATE_DISABLED, |acc, r| {
+            if acc == SAMPLE_RATE_DISABLED || r > acc {
+                r
+            } else {
+                acc
+            }
+        });
+
+    let s = state();
+    s.rates = rates;
+    s.rate = rate;
+    s.next_call = 0;
+
+    // report the physical sensors
+
+    let mut required = [None; BSEC_MAX_PHYSICAL_SENSOR as usize];
+    let mut n = 0;
+    if rate != SAMPLE_RATE_DISABLED {
+        required[n] = Some(BSEC_INPUT_PRESSURE);
+        n += 1;
+        required[n] = Some(BSEC_INPUT_HUMIDITY);
+        n += 1;
+        required[n] = Some(BSEC_INPUT_TEMPERATURE);
+        n += 1;
+        if s.gas_enabled() {
+            required[n] = Some(BSEC_INPUT_GASRESISTOR);
+            n += 1;
+        }
+        if s.enabled(BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_TEMPERATURE)
+            || s.enabled(BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_HUMIDITY)
+        {
+            required[n] = Some(BSEC_INPUT_HEATSOURCE);
+            n += 1;
+        }
+    }
+
+    if n > *n_required_sensor_settings as usize {
+        return BSEC_E_SU_GATECOUNTEXCEEDSARRAY;
+    }
+
+    for (i, sensor) in required.iter().flatten().enumerate() {
+        *required_sensor_settings.add(i) = bsec_sensor_configuration_t {
+            sample_rate: rate,
+            sensor_id: *sensor as u8,
+        };
+    }
+    *n_required_sensor_settings = n as u8;
+
+    match warning {
+        Some(warning) => warning,
+        None if requested.is_empty() => BSEC_I_SU_SUBSCRIBEDOUTPUTGATES,
+        None => BSEC_OK,
+    }
+}
+
+pub unsafe fn bsec_sensor_control(
+    time_stamp: i64,
+    sensor_settings: *mut bsec_bme_settings_t,
+) -> bsec_library_return_t {
+    let s = state();
+
+    let period = match s.period() {
+        Some(period) => period,
+        None => {
+            *sensor_settings = bsec_bme_settings_t {
+                next_call: time_stamp + 1_000_000_000,
+                process_data: 0,
+                heater_temperature: 0,
+                heating_duration: 0,
+                run_gas: 0,
+                pressure_oversampling: 0,
+                temperature_oversampling: 0,
+                humidity_oversampling: 0,
+                trigger_measurement: 0,
+            };
+            return BSEC_OK;
+        }
+    };
+
+    // allow for some jitter, like the real library does
+    let tolerance = period / 16;
+
+    let result = if s.next_call != 0 && (time_stamp - s.next_call).abs() > tolerance {
+        BSEC_W_SC_CALL_TIMING_VIOLATION
+    } else {
+        BSEC_OK
+    };
+
+    if s.next_call != 0 && time_stamp < s.next_call - tolerance {
+        // too early, no measurement
+        *sensor_settings = bsec_bme_settings_t {
+            next_call: s.next_call,
+            process_data: 0,
+            heater_temperature: 0,
+            heating_duration: 0,
+            run_gas: 0,
+            pressure_oversampling: 0,
+            temperature_oversampling: 0,
+            humidity_oversampling: 0,
+            trigger_measurement: 0,
+        };
+        return result;
+    }
+
+    let run_gas = s.gas_enabled();
+    let mut process_data = (1 << (BSEC_INPUT_PRESSURE as u32 - 1))
+        | (1 << (BSEC_INPUT_HUMIDITY as u32 - 1))
+        | (1 << (BSEC_INPUT_TEMPERATURE as u32 - 1));
+    if run_gas {
+        process_data |= 1 << (BSEC_INPUT_GASRESISTOR as u32 - 1);
+    }
+
+    s.next_call = time_stamp + period;
+
+    *sensor_settings = bsec_bme_settings_t {
+        next_call: s.next_call,
+        process_data,
+        heater_temperature: if run_gas { HEATER_TEMPERATURE } else { 0 },
+        heating_duration: if run_gas { HEATING_DURATION } else { 0 },
+        run_gas: run_gas as u8,
+        pressure_oversampling: 1,
+        temperature_oversampling: 1,
+        humidity_oversampling: 1,
+        trigger_measurement: 1,
+    };
+
+    result
+}
+
+pub unsafe fn bsec_do_steps(
+    inputs: *const bsec_input_t,
+    n_inputs: u8,
+    outputs: *mut bsec_output_t,
+    n_outputs: *mut u8,
+) -> bsec_library_return_t {
+    let inputs = core::slice::from_raw_parts(inputs, n_inputs as usize);
+    let max_outputs = *n_outputs as usize;
+    *n_outputs = 0;
+
+    let mut temperature = None;
+    let mut humidity = None;
+    let mut pressure = None;
+    let mut gas = None;
+    let mut heat_source = 0.0f32;
+    let mut time_stamp = 0i64;
+
+    for (i, input) in inputs.iter().enumerate() {
+        if inputs[..i].iter().any(|p| p.sensor_id == input.sensor_id) {
+            return BSEC_E_DOSTEPS_DUPLICATEINPUT;
+        }
+        if i > 0 && input.time_stamp != time_stamp {
+            return BSEC_W_DOSTEPS_TSINTRADIFFOUTOFRANGE;
+        }
+        time_stamp = input.time_stamp;
+
+        let signal = input.signal;
+        match input.sensor_id {
+            id if id == BSEC_INPUT_PRESSURE as u8 => pressure = Some(signal),
+            id if id == BSEC_INPUT_HUMIDITY as u8 => humidity = Some(signal),
+            id if id == BSEC_INPUT_TEMPERATURE as u8 => temperature = Some(signal),
+            id if id == BSEC_INPUT_GASRESISTOR as u8 => {
+                if signal <= 0.0 {
+                    return BSEC_E_DOSTEPS_VALUELIMITS;
+                }
+                gas = Some(signal)
+            }
+            id if id == BSEC_INPUT_HEATSOURCE as u8 => heat_source = signal,
+            id if id == BSEC_INPUT_DISABLE_BASELINE_TRACKER as u8 => {
+                state().baseline_tracker_disabled = signal != 0.0
+            }
+            _ => return BSEC_E_DOSTEPS_INVALIDINPUT,
+        }
+    }
+
+    let s = state();
+
+    if let Some(gas) = gas {
+        s.samples = s.samples.saturating_add(1);
+        if !s.baseline_tracker_disabled && gas > s.baseline {
+            s.baseline = gas;
+        }
+    }
+
+    let accuracy = s.accuracy();
+    let gas_values = gas.map(|gas| {
+        let ratio = s.baseline / gas;
+        let iaq = (25.0 * ratio * ratio).clamp(0.0, 500.0);
+        (gas, iaq)
+    });
+
+    let mut n = 0usize;
+    let mut result = BSEC_OK;
+
+    for sensor in bsec_virtual_sensor_t::ALL.iter() {
+        if !s.enabled(*sensor) {
+            continue;
+        }
+
+        let value = match (*sensor, gas_values) {
+            (BSEC_OUTPUT_IAQ, Some((_, iaq))) => Some((iaq, accuracy)),
+            (BSEC_OUTPUT_STATIC_IAQ, Some((_, iaq))) => Some((iaq, accuracy)),
+            (BSEC_OUTPUT_CO2_EQUIVALENT, Some((_, iaq))) => {
+                Some(((500.0 + (iaq - 25.0) * 10.0).max(400.0), accuracy))
+            }
+            (BSEC_OUTPUT_BREATH_VOC_EQUIVALENT, Some((_, iaq))) => {
+                Some(((0.5 + (iaq - 25.0) * 0.02).max(0.0), accuracy))
+            }
+            (BSEC_OUTPUT_RAW_GAS, Some((gas, _))) => Some((gas, 0)),
+            (BSEC_OUTPUT_STABILIZATION_STATUS, Some(_)) => Some((
+                if s.samples >= STABILIZATION_SAMPLES {
+                    1.0
+                } else {
+                    0.0
+                },
+                0,
+            )),
+            (BSEC_OUTPUT_RUN_IN_STATUS, Some(_)) => Some((
+                if s.samples >= RUN_IN_SAMPLES { 1.0 } else { 0.0 },
+                0,
+            )),
+            (BSEC_OUTPUT_COMPENSATED_GAS, Some((gas, _))) => Some((libm::logf(gas), accuracy)),
+            (BSEC_OUTPUT_GAS_PERCENTAGE, Some((gas, _))) => {
+                Some(((100.0 * gas / s.baseline).min(100.0), accuracy))
+            }
+            (BSEC_OUTPUT_RAW_TEMPERATURE, _) => temperature.map(|t| (t, 0)),
+            (BSEC_OUTPUT_RAW_PRESSURE, _) => pressure.map(|p| (p, 0)),
+            (BSEC_OUTPUT_RAW_HUMIDITY, _) => humidity.map(|h| (h, 0)),
+            (BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_TEMPERATURE, _) => {
+                temperature.map(|t| (t - heat_source, 0))
+            }
+            (BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_HUMIDITY, _) => {
+                // relative humidity rises about 6% per degree the air cools down
+                humidity.map(|h| ((h * (1.0 + 0.06 * heat_source)).min(100.0), 0))
+            }
+            _ => None,
+        };
+
+        if let Some((signal, accuracy)) = value {
+            if max_outputs == 0 {
+                return BSEC_I_DOSTEPS_NOOUTPUTSRETURNABLE;
+            }
+            if n >= max_outputs {
+                result = BSEC_W_DOSTEPS_EXCESSOUTPUTS;
+                break;
+            }
+            *outputs.add(n) = bsec_output_t {
+                time_stamp,
+                signal,
+                signal_dimensions: 1,
+                sensor_id: *sensor as u8,
+                accuracy,
+            };
+            n += 1;
+        }
+    }
+
+    *n_outputs = n as u8;
+    result
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b))
+}
+
+pub unsafe fn bsec_get_state(
+    _state_set_id: u8,
+    serialized_state: *mut u8,
+    n_serialized_state_max: u32,
+    _work_buffer: *mut u8,
+    n_work_buffer: u32,
+    n_serialized_state: *mut u32,
+) -> bsec_library_return_t {
+    *n_serialized_state = 0;
+
+    if (n_serialized_state_max as usize) < STATE_SIZE {
+        return BSEC_E_CONFIG_INSUFFICIENTBUFFER;
+    }
+    if (n_work_buffer as usize) < STATE_SIZE {
+        return BSEC_E_CONFIG_INSUFFICIENTWORKBUFFER;
+    }
+
+    let s = state();
+    let mut blob = [0u8; STATE_SIZE];
+    blob[0..4].copy_from_slice(&(STATE_SIZE as u32).to_le_bytes());
+    blob[4..8].copy_from_slice(&STATE_MAGIC);
+    blob[8..12].copy_from_slice(&s.samples.to_le_bytes());
+    blob[12..16].copy_from_slice(&s.baseline.to_le_bytes());
+    blob[16] = checksum(&blob[..16]);
+
+    core::ptr::copy_nonoverlapping(blob.as_ptr(), serialized_state, STATE_SIZE);
+    *n_serialized_state = STATE_SIZE as u32;
+
+    BSEC_OK
+}
+
+pub unsafe fn bsec_set_state(
+    serialized_state: *const u8,
+    n_serialized_state: u32,
+    _work_buffer: *mut u8,
+    n_work_buffer_size: u32,
+) -> bsec_library_return_t {
+    if (n_serialized_state as usize) < 4 {
+        return BSEC_E_CONFIG_EMPTY;
+    }
+    if (n_work_buffer_size as usize) < STATE_SIZE {
+        return BSEC_E_CONFIG_INSUFFICIENTWORKBUFFER;
+    }
+
+    let blob = core::slice::from_raw_parts(serialized_state, n_serialized_state as usize);
+
+    let mut len = [0u8; 4];
+    len.copy_from_slice(&blob[0..4]);
+    if u32::from_le_bytes(len) != n_serialized_state {
+        return BSEC_E_CONFIG_INVALIDSTRINGSIZE;
+    }
+    if blob.len() != STATE_SIZE || blob[4..8] != STATE_MAGIC {
+        return BSEC_E_CONFIG_VERSIONMISMATCH;
+    }
+    if checksum(&blob[..16]) != blob[16] {
+        return BSEC_E_CONFIG_CRCMISMATCH;
+    }
+
+    let mut samples = [0u8; 4];
+    samples.copy_from_slice(&blob[8..12]);
+    let mut baseline = [0u8; 4];
+    baseline.copy_from_slice(&blob[12..16]);
+
+    let s = state();
+    s.samples = u32::from_le_bytes(samples);
+    s.baseline = f32::from_le_bytes(baseline);
+
+    BSEC_OK
+}
+
+pub unsafe fn bsec_set_configuration(
+    _serialized_settings: *const u8,
+    n_serialized_settings: u32,
+    _work_buffer: *mut u8,
+    n_work_buffer_size: u32,
+) -> bsec_library_return_t {
+    if n_serialized_settings < 4 {
+        return BSEC_E_CONFIG_EMPTY;
+    }
+    if n_serialized_settings > BSEC_MAX_PROPERTY_BLOB_SIZE {
+        return BSEC_E_CONFIG_INVALIDSTRINGSIZE;
+    }
+    if n_work_buffer_size < BSEC_MAX_WORKBUFFER_SIZE {
+        return BSEC_E_CONFIG_INSUFFICIENTWORKBUFFER;
+    }
+
+    BSEC_OK
+}
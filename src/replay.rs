@@ -0,0 +1,784 @@
+//! Replay recorded measurements through BSEC on the host.
+//!
+//! The measurements are either read from a trace, recorded with the `dump` feature, or from a
+//! CSV file. A trace is replayed call by call using [`Replayer::replay`]: each call to
+//! [`Bsec::sensor_control`] and [`Bsec::process_data`] is repeated at its recorded timestamp,
+//! with the recorded inputs. CSV measurements have no recorded schedule, so [`Replayer::run`]
+//! follows the schedule of BSEC in simulated time, providing each measurement when BSEC requests
+//! it. The outputs can be written as CSV or JSON using the [`OutputWriter`], and compared to the
+//! outputs recorded in the trace using the [`Comparator`].
+//!
+//! A trace only holds the calls made while its sink was set. If the sink was set on an instance
+//! which was already in use, the configuration, state and subscription it had are unknown, and
+//! the replay may diverge from the recording. [`Recording::fresh_instance`] tells if the trace
+//! starts from a fresh instance, [`Recording::from_trace`] warns if it doesn't.
+
+use crate::trace::{
+    Record, RecordedSettings, RecordedSignal, Trace, TraceError, FLAG_BSEC2, FLAG_FRESH_INSTANCE,
+};
+use crate::{
+    bsec_physical_sensor_t, bsec_virtual_sensor_t, Accuracy, Bsec, Control, Error, Inputs,
+    Measurement, Outputs, SampleRate, Subscription, Timestamp,
+};
+use core::convert::TryFrom;
+use core::fmt::{Display, Formatter};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::string::{String, ToString};
+use std::time::Duration;
+use std::vec::Vec;
+
+/// An error of the replay.
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Bsec(Error),
+    Trace(TraceError),
+    /// The trace was recorded with a different major version of BSEC than this build uses.
+    Library {
+        /// The trace was recorded with BSEC 2.x.
+        bsec2: bool,
+    },
+    /// A line of the CSV input is invalid.
+    Csv {
+        line: usize,
+        message: String,
+    },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "I/O error: {}", err),
+            ReplayError::Bsec(err) => write!(f, "BSEC: {}", err),
+            ReplayError::Trace(err) => write!(f, "Trace: {}", err),
+            ReplayError::Library { bsec2 } => {
+                let name = |bsec2: bool| if bsec2 { "BSEC 2.x" } else { "BSEC 1.x" };
+                write!(
+                    f,
+                    "The trace was recorded with {}, but this build uses {}",
+                    name(*bsec2),
+                    name(cfg!(feature = "bsec2"))
+                )
+            }
+            ReplayError::Csv { line, message } => write!(f, "CSV, line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl From<Error> for ReplayError {
+    fn from(err: Error) -> Self {
+        ReplayError::Bsec(err)
+    }
+}
+
+impl From<TraceError> for ReplayError {
+    fn from(err: TraceError) -> Self {
+        ReplayError::Trace(err)
+    }
+}
+
+/// A measurement, at a point in time.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub timestamp: Timestamp,
+    pub measurement: Measurement,
+}
+
+/// A recorded call to the BSEC library, which is repeated by [`Replayer::replay`].
+#[derive(Clone, Debug)]
+pub enum Step {
+    /// A call to `bsec_set_configuration`, with the configuration.
+    SetConfiguration(Vec<u8>),
+    /// A call to `bsec_set_state`, with the state.
+    SetState(Vec<u8>),
+    /// A call to `bsec_update_subscription`.
+    UpdateSubscription(Subscription),
+    /// A call to `bsec_sensor_control`, and the settings it returned.
+    SensorControl {
+        timestamp: Timestamp,
+        settings: RecordedSettings,
+    },
+    /// A call to `bsec_do_steps`, with all of its inputs.
+    DoSteps {
+        timestamp: Timestamp,
+        inputs: Inputs,
+    },
+}
+
+/// The outputs of a single call to `bsec_do_steps`, recorded in a trace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedOutputs {
+    pub timestamp: Timestamp,
+    pub outputs: Vec<RecordedSignal>,
+}
+
+/// The data of a trace, prepared for replaying it.
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    /// The trace was started before the first call to the instance, so it holds all calls which
+    /// changed its state.
+    pub fresh_instance: bool,
+    /// The successful calls to the BSEC library, in order.
+    pub steps: Vec<Step>,
+    pub outputs: Vec<RecordedOutputs>,
+}
+
+impl Recording {
+    /// Extract the calls and the outputs of a trace.
+    ///
+    /// Fails if the trace was recorded with a different major version of BSEC than this build
+    /// uses, as its inputs and outputs aren't compatible.
+    pub fn from_trace(trace: &Trace) -> Result<Self, ReplayError> {
+        let bsec2 = trace.flags & FLAG_BSEC2 != 0;
+        if bsec2 != cfg!(feature = "bsec2") {
+            return Err(ReplayError::Library { bsec2 });
+        }
+
+        let mut recording = Recording {
+            fresh_instance: trace.flags & FLAG_FRESH_INSTANCE != 0,
+            ..Default::default()
+        };
+        if !recording.fresh_instance {
+            log::warn!("The trace doesn't start from a fresh instance, the replay may diverge");
+        }
+
+        for record in &trace.records {
+            match record {
+                // failed calls don't change the state of BSEC, and would abort the replay
+                Record::UpdateSubscription { result, .. }
+                | Record::SensorControl { result, .. }
+                | Record::DoSteps { result, .. }
+                | Record::SetConfiguration { result, .. }
+                | Record::SetState { result, .. }
+                    if *result < 0 => {}
+                Record::SetConfiguration { configuration, .. } => recording
+                    .steps
+                    .push(Step::SetConfiguration(configuration.clone())),
+                Record::SetState { state, .. } => {
+                    recording.steps.push(Step::SetState(state.clone()))
+                }
+                Record::UpdateSubscription { requested, .. } => {
+                    let subscription =
+                        requested.iter().fold(Subscription::new(), |s, sensor| {
+                            match (
+                                bsec_virtual_sensor_t::try_from(sensor.sensor_id),
+                                SampleRate::from_frequency(sensor.sample_rate),
+                            ) {
+                                (Ok(id), Some(rate)) => s.with(id, rate),
+                                _ => s,
+                            }
+                        });
+                    recording.steps.push(Step::UpdateSubscription(subscription));
+                }
+                Record::SensorControl {
+                    timestamp,
+                    settings,
+                    ..
+                } => recording.steps.push(Step::SensorControl {
+                    timestamp: *timestamp,
+                    settings: *settings,
+                }),
+                Record::DoSteps {
+                    inputs, outputs, ..
+                } => {
+                    // the inputs and outputs all carry the timestamp of the call
+                    let timestamp = match inputs.first().or_else(|| outputs.first()) {
+                        Some(signal) => signal.timestamp,
+                        None => continue,
+                    };
+                    recording.steps.push(Step::DoSteps {
+                        timestamp,
+                        inputs: recorded_inputs(inputs),
+                    });
+                    recording.outputs.push(RecordedOutputs {
+                        timestamp,
+                        outputs: outputs.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(recording)
+    }
+}
+
+fn recorded_inputs(signals: &[RecordedSignal]) -> Inputs {
+    let mut inputs = Inputs::default();
+
+    for input in signals {
+        let signal = Some(input.signal);
+        match bsec_physical_sensor_t::try_from(input.sensor_id) {
+            Ok(bsec_physical_sensor_t::BSEC_INPUT_TEMPERATURE) => inputs.temperature = signal,
+            Ok(bsec_physical_sensor_t::BSEC_INPUT_HUMIDITY) => inputs.humidity = signal,
+            Ok(bsec_physical_sensor_t::BSEC_INPUT_PRESSURE) => inputs.pressure = signal,
+            Ok(bsec_physical_sensor_t::BSEC_INPUT_GASRESISTOR) => inputs.gas_resistance = signal,
+            Ok(bsec_physical_sensor_t::BSEC_INPUT_HEATSOURCE) => inputs.heat_source = signal,
+            Ok(bsec_physical_sensor_t::BSEC_INPUT_DISABLE_BASELINE_TRACKER) => {
+                inputs.disable_baseline_tracker = Some(input.signal != 0.0)
+            }
+            #[cfg(feature = "bsec2")]
+            Ok(bsec_physical_sensor_t::BSEC_INPUT_PROFILE_PART) => {
+                inputs.profile_part = Some(input.signal as u8)
+            }
+            _ => log::warn!("Skipping unsupported input: {}", input.sensor_id),
+        }
+    }
+
+    inputs
+}
+
+/// Read measurements from CSV.
+///
+/// The columns are: the timestamp (in milliseconds), temperature (°C), relative humidity (%),
+/// pressure (Pa) and gas resistance (Ohm). Pressure and gas resistance may be empty. A first
+/// line which doesn't start with a number is treated as header, and skipped.
+pub fn read_csv<R: BufRead>(reader: R) -> Result<Vec<Sample>, ReplayError> {
+    let mut samples = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        let number = index + 1;
+
+        if line.is_empty() || (number == 1 && !line.starts_with(|c: char| c.is_ascii_digit())) {
+            continue;
+        }
+
+        let error = |message: &str| ReplayError::Csv {
+            line: number,
+            message: message.to_string(),
+        };
+
+        let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+        if columns.len() < 3 {
+            return Err(error(
+                "Expected at least timestamp, temperature and humidity",
+            ));
+        }
+        let number = |column: usize| -> Result<Option<f32>, ReplayError> {
+            match columns.get(column) {
+                None | Some(&"") => Ok(None),
+                Some(value) => value.parse().map(Some).map_err(|_| error("Invalid number")),
+            }
+        };
+
+        let timestamp = columns[0]
+            .parse::<i64>()
+            .map_err(|_| error("Invalid timestamp"))?
+            .checked_mul(1_000_000)
+            .map(Timestamp::from_nanos)
+            .ok_or_else(|| error("Timestamp out of range"))?;
+        let required = |value: Option<f32>| value.ok_or_else(|| error("Missing value"));
+
+        samples.push(Sample {
+            timestamp,
+            measurement: Measurement {
+                temperature: required(number(1)?)?,
+                humidity: required(number(2)?)?,
+                pressure: number(3)?,
+                gas_resistance: number(4)?,
+            },
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Runs the loop of BSEC over recorded samples, in simulated time.
+pub struct Replayer<'b> {
+    bsec: &'b mut Bsec,
+    acceleration: Option<f64>,
+}
+
+/// The result of [`Replayer::replay`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Replayed {
+    /// The number of processed measurements.
+    pub processed: usize,
+    /// The number of calls to `bsec_sensor_control`, which returned different settings than
+    /// recorded.
+    pub diverged: usize,
+}
+
+impl<'b> Replayer<'b> {
+    /// Create a new replayer.
+    ///
+    /// [`Replayer::run`] requires the subscription of the `bsec` instance to be set up already.
+    /// [`Replayer::replay`] applies the recorded one, but a trace which doesn't start from a fresh
+    /// instance may lack it.
+    pub fn new(bsec: &'b mut Bsec) -> Self {
+        Replayer {
+            bsec,
+            acceleration: None,
+        }
+    }
+
+    /// Pace the replay in real time, accelerated by `factor`. By default, the replay runs as
+    /// fast as possible.
+    pub fn with_acceleration(mut self, factor: f64) -> Self {
+        self.acceleration = Some(factor).filter(|factor| *factor > 0.0);
+        self
+    }
+
+    /// Replay the samples, which must be ordered by their timestamp.
+    ///
+    /// The simulated time starts at the first sample, and follows the schedule of BSEC. When
+    /// BSEC requests a measurement, the latest sample at that time is provided. Each processed
+    /// measurement is passed to `sink`, with its inputs and outputs. Returns the number of
+    /// processed measurements.
+    pub fn run<F>(&mut self, samples: &[Sample], mut sink: F) -> Result<usize, ReplayError>
+    where
+        F: FnMut(Timestamp, &Inputs, &Outputs) -> Result<(), ReplayError>,
+    {
+        let (first, last) = match (samples.first(), samples.last()) {
+            (Some(first), Some(last)) => (first.timestamp, last.timestamp),
+            _ => return Ok(0),
+        };
+
+        let mut now = first;
+        let mut index = 0;
+        let mut processed = 0;
+
+        while now <= last {
+            let control = self.bsec.sensor_control(now)?;
+
+            if control.trigger_measurement {
+                while index + 1 < samples.len() && samples[index + 1].timestamp <= now {
+                    index += 1;
+                }
+
+                let inputs = control.measurement_inputs(&samples[index].measurement);
+                let outputs = self.bsec.process_data(now, &inputs)?;
+                sink(now, &inputs, &outputs)?;
+                processed += 1;
+            }
+
+            // make progress, even if BSEC doesn't schedule a later call
+            let next = control
+                .next_call
+                .max(Timestamp::from_nanos(now.as_nanos() + 1_000_000));
+            self.pace(now, next);
+            now = next;
+        }
+
+        Ok(processed)
+    }
+
+    /// Repeat the recorded calls of a [`Recording`], at their recorded timestamps.
+    ///
+    /// The configuration, state and subscription are applied as recorded. The inputs of each call to `bsec_do_steps` are passed unchanged, and the outputs are
+    /// passed to `sink`, with the inputs. The settings returned by `bsec_sensor_control` are
+    /// compared to the recorded ones, and differences are logged and counted, as they show that
+    /// the replay diverged from the recording.
+    pub fn replay<F>(&mut self, steps: &[Step], mut sink: F) -> Result<Replayed, ReplayError>
+    where
+        F: FnMut(Timestamp, &Inputs, &Outputs) -> Result<(), ReplayError>,
+    {
+        let mut replayed = Replayed::default();
+        let mut last = None;
+
+        for step in steps {
+            match step {
+                Step::SetConfiguration(configuration) => {
+                    self.bsec.set_configuration(configuration)?
+                }
+                Step::SetState(state) => self.bsec.set_state(state)?,
+                Step::UpdateSubscription(subscription) => {
+                    self.bsec.subscribe(subscription)?;
+                }
+                Step::SensorControl {
+                    timestamp,
+                    settings,
+                } => {
+                    if let Some(last) = last.replace(*timestamp) {
+                        self.pace(last, *timestamp);
+                    }
+                    let control = self.bsec.sensor_control(*timestamp)?;
+                    if !same_settings(&control, settings) {
+                        log::warn!(
+                            "Settings at {} ms differ: {:?}, recorded {:?}",
+                            timestamp.as_millis(),
+                            control,
+                            settings
+                        );
+                        replayed.diverged += 1;
+                    }
+                }
+                Step::DoSteps { timestamp, inputs } => {
+                    if let Some(last) = last.replace(*timestamp) {
+                        self.pace(last, *timestamp);
+                    }
+                    let outputs = self.bsec.process_data(*timestamp, inputs)?;
+                    sink(*timestamp, inputs, &outputs)?;
+                    replayed.processed += 1;
+                }
+            }
+        }
+
+        Ok(replayed)
+    }
+
+    /// Sleep for the time between `from` and `to`, when pacing the replay.
+    fn pace(&self, from: Timestamp, to: Timestamp) {
+        if let Some(factor) = self.acceleration {
+            let nanos = (to.as_nanos() - from.as_nanos()).max(0) as f64 / factor;
+            std::thread::sleep(Duration::from_nanos(nanos as u64));
+        }
+    }
+}
+
+fn same_settings(control: &Control, settings: &RecordedSettings) -> bool {
+    control.next_call == settings.next_call
+        && control.process_data.bits() == settings.process_data
+        && control.heater_temperature == settings.heater_temperature
+        && control.heating_duration.0 == u32::from(settings.heating_duration)
+        && control.run_gas == settings.run_gas
+        && u8::from(control.pressure_oversampling) == settings.pressure_oversampling
+        && u8::from(control.temperature_oversampling) == settings.temperature_oversampling
+        && u8::from(control.humidity_oversampling) == settings.humidity_oversampling
+        && control.trigger_measurement == settings.trigger_measurement
+}
+
+/// An output, as written by the [`OutputWriter`].
+struct Field {
+    name: &'static str,
+    sensor: Option<bsec_virtual_sensor_t>,
+    value: Option<f32>,
+    /// The accuracy, for outputs which report one.
+    accuracy: Option<Option<u8>>,
+}
+
+impl Field {
+    fn plain(name: &'static str, sensor: bsec_virtual_sensor_t, value: Option<f32>) -> Self {
+        Field {
+            name,
+            sensor: Some(sensor),
+            value,
+            accuracy: None,
+        }
+    }
+
+    fn accuracy(
+        name: &'static str,
+        sensor: bsec_virtual_sensor_t,
+        value: &Option<Accuracy<f32>>,
+    ) -> Self {
+        Field {
+            name,
+            sensor: Some(sensor),
+            value: value.as_ref().and_then(|v| v.value().copied()),
+            accuracy: Some(value.as_ref().map(Accuracy::accuracy)),
+        }
+    }
+}
+
+fn fields(outputs: &Outputs) -> Vec<Field> {
+    use bsec_virtual_sensor_t::*;
+
+    let mut fields = vec![
+        Field::accuracy("iaq", BSEC_OUTPUT_IAQ, &outputs.iaq),
+        Field::accuracy("static_iaq", BSEC_OUTPUT_STATIC_IAQ, &outputs.static_iaq),
+        Field::accuracy(
+            "co2_equivalent",
+            BSEC_OUTPUT_CO2_EQUIVALENT,
+            &outputs.co2_equivalent,
+        ),
+        Field::accuracy(
+            "breath_voc_equivalent",
+            BSEC_OUTPUT_BREATH_VOC_EQUIVALENT,
+            &outputs.breath_voc_equivalent,
+        ),
+        Field::plain(
+            "raw_temperature",
+            BSEC_OUTPUT_RAW_TEMPERATURE,
+            outputs.raw_temperature,
+        ),
+        Field::plain(
+            "raw_pressure",
+            BSEC_OUTPUT_RAW_PRESSURE,
+            outputs.raw_pressure,
+        ),
+        Field::plain(
+            "raw_humidity",
+            BSEC_OUTPUT_RAW_HUMIDITY,
+            outputs.raw_humidity,
+        ),
+        Field::plain(
+            "raw_gas_resistance",
+            BSEC_OUTPUT_RAW_GAS,
+            outputs.raw_gas_resistance,
+        ),
+        Field::plain(
+            "stabilization_status",
+            BSEC_OUTPUT_STABILIZATION_STATUS,
+            outputs.stabilization_status,
+        ),
+        Field::plain(
+            "run_in_status",
+            BSEC_OUTPUT_RUN_IN_STATUS,
+            outputs.run_in_status,
+        ),
+        Field::plain(
+            "sensor_heat_compensated_temperature",
+            BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_TEMPERATURE,
+            outputs.sensor_heat_compensated_temperature,
+        ),
+        Field::plain(
+            "sensor_heat_compensated_humidity",
+            BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_HUMIDITY,
+            outputs.sensor_heat_compensated_humidity,
+        ),
+        #[cfg(not(feature = "bsec2"))]
+        Field::accuracy(
+            "compensated_gas",
+            BSEC_OUTPUT_COMPENSATED_GAS,
+            &outputs.compensated_gas,
+        ),
+        Field::accuracy(
+            "gas_percentage",
+            BSEC_OUTPUT_GAS_PERCENTAGE,
+            &outputs.gas_percentage,
+        ),
+    ];
+
+    #[cfg(feature = "bsec2")]
+    fields.extend(vec![
+        Field::accuracy(
+            "gas_estimate_1",
+            BSEC_OUTPUT_GAS_ESTIMATE_1,
+            &outputs.gas_estimate_1,
+        ),
+        Field::accuracy(
+            "gas_estimate_2",
+            BSEC_OUTPUT_GAS_ESTIMATE_2,
+            &outputs.gas_estimate_2,
+        ),
+        Field::accuracy(
+            "gas_estimate_3",
+            BSEC_OUTPUT_GAS_ESTIMATE_3,
+            &outputs.gas_estimate_3,
+        ),
+        Field::accuracy(
+            "gas_estimate_4",
+            BSEC_OUTPUT_GAS_ESTIMATE_4,
+            &outputs.gas_estimate_4,
+        ),
+        Field::plain(
+            "raw_gas_index",
+            BSEC_OUTPUT_RAW_GAS_INDEX,
+            outputs.raw_gas_index,
+        ),
+    ]);
+
+    fields.insert(
+        0,
+        Field {
+            name: "timestamp_ms",
+            sensor: None,
+            value: None,
+            accuracy: None,
+        },
+    );
+
+    fields
+}
+
+/// The format of the [`OutputWriter`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// One line per measurement, starting with a header line.
+    Csv,
+    /// An array, with an object per measurement.
+    Json,
+}
+
+/// Writes the outputs of BSEC, one row or object per measurement.
+///
+/// The names of the columns or keys are the names of the fields of [`Outputs`], starting with
+/// `timestamp_ms`. Outputs with an accuracy get an additional `<name>_accuracy` column, from 0
+/// (unreliable) to 3 (high). Missing values are empty in CSV, and `null` in JSON.
+pub struct OutputWriter<W: Write> {
+    writer: W,
+    format: Format,
+    rows: usize,
+}
+
+impl<W: Write> OutputWriter<W> {
+    pub fn new(writer: W, format: Format) -> Self {
+        OutputWriter {
+            writer,
+            format,
+            rows: 0,
+        }
+    }
+
+    pub fn write(&mut self, timestamp: Timestamp, outputs: &Outputs) -> std::io::Result<()> {
+        let fields = fields(outputs);
+        let timestamp = timestamp.as_millis().to_string();
+
+        let mut columns: Vec<(String, Option<String>)> = Vec::new();
+        for field in &fields {
+            if field.sensor.is_none() {
+                columns.push((field.name.to_string(), Some(timestamp.clone())));
+                continue;
+            }
+            columns.push((
+                field.name.to_string(),
+                field.value.filter(|v| v.is_finite()).map(|v| v.to_string()),
+            ));
+            if let Some(accuracy) = field.accuracy {
+                columns.push((
+                    format!("{}_accuracy", field.name),
+                    accuracy.map(|a| a.to_string()),
+                ));
+            }
+        }
+
+        match self.format {
+            Format::Csv => {
+                if self.rows == 0 {
+                    let names: Vec<&str> = columns.iter().map(|(name, _)| name.as_str()).collect();
+                    writeln!(self.writer, "{}", names.join(","))?;
+                }
+                let values: Vec<&str> = columns
+                    .iter()
+                    .map(|(_, value)| value.as_deref().unwrap_or(""))
+                    .collect();
+                writeln!(self.writer, "{}", values.join(","))?;
+            }
+            Format::Json => {
+                let values: Vec<String> = columns
+                    .iter()
+                    .map(|(name, value)| {
+                        format!("\"{}\":{}", name, value.as_deref().unwrap_or("null"))
+                    })
+                    .collect();
+                let separator = if self.rows == 0 { "[\n" } else { ",\n" };
+                write!(self.writer, "{}{{{}}}", separator, values.join(","))?;
+            }
+        }
+
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Complete the output, returning the writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if self.format == Format::Json {
+            let end = if self.rows == 0 { "[]\n" } else { "\n]\n" };
+            self.writer.write_all(end.as_bytes())?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Compares the outputs of a replay to the outputs recorded in a trace.
+pub struct Comparator {
+    recorded: BTreeMap<i64, Vec<RecordedSignal>>,
+    tolerance: f32,
+    report: Comparison,
+}
+
+/// The result of a [`Comparator`].
+#[derive(Clone, Debug, Default)]
+pub struct Comparison {
+    /// The number of replayed measurements, which had recorded outputs.
+    pub compared: usize,
+    /// The number of replayed measurements, without recorded outputs.
+    pub unmatched: usize,
+    /// The number of outputs, differing by more than the tolerance, or in their accuracy.
+    pub mismatches: usize,
+    /// The largest difference of each output, by name.
+    pub max_differences: BTreeMap<&'static str, f32>,
+}
+
+impl Comparison {
+    /// Whether outputs were compared, all replayed measurements had recorded outputs, and none
+    /// of them differ.
+    pub fn is_match(&self) -> bool {
+        self.compared > 0 && self.unmatched == 0 && self.mismatches == 0
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        writeln!(
+            f,
+            "Compared: {}, without recorded outputs: {}, mismatches: {}",
+            self.compared, self.unmatched, self.mismatches
+        )?;
+        for (name, difference) in &self.max_differences {
+            writeln!(f, "  {}: max difference {}", name, difference)?;
+        }
+        Ok(())
+    }
+}
+
+impl Comparator {
+    /// Create a comparator, allowing each output to differ by `tolerance`.
+    pub fn new(recorded: &[RecordedOutputs], tolerance: f32) -> Self {
+        Comparator {
+            recorded: recorded
+                .iter()
+                .map(|r| (r.timestamp.as_nanos(), r.outputs.clone()))
+                .collect(),
+            tolerance,
+            report: Default::default(),
+        }
+    }
+
+    /// Compare the replayed outputs, of the measurement at `timestamp`.
+    pub fn compare(&mut self, timestamp: Timestamp, outputs: &Outputs) {
+        let recorded = match self.recorded.get(&timestamp.as_nanos()) {
+            Some(recorded) => recorded,
+            None => {
+                self.report.unmatched += 1;
+                return;
+            }
+        };
+        self.report.compared += 1;
+
+        let fields = fields(outputs);
+        for signal in recorded {
+            let field = match fields
+                .iter()
+                .find(|f| f.sensor.map(|s| s as u8) == Some(signal.sensor_id))
+            {
+                Some(field) => field,
+                None => continue,
+            };
+
+            let accuracy_matches = match field.accuracy {
+                Some(accuracy) => accuracy == Some(signal.accuracy),
+                None => true,
+            };
+            let difference = match field.value {
+                Some(value) => (value - signal.signal).abs(),
+                // unreliable outputs don't carry a value
+                None if field.accuracy == Some(Some(0)) => 0.0,
+                None => f32::INFINITY,
+            };
+
+            let max = self.report.max_differences.entry(field.name).or_default();
+            *max = max.max(difference);
+
+            if !accuracy_matches || difference > self.tolerance {
+                self.report.mismatches += 1;
+            }
+        }
+    }
+
+    pub fn finish(self) -> Comparison {
+        self.report
+    }
+}
@@ -0,0 +1,95 @@
+//! The abstraction of the sensor, which BSEC processes the measurements of.
+
+use crate::Control;
+use core::fmt::Debug;
+
+/// The oversampling of a measurement, requested by BSEC.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Oversampling {
+    /// The measurement is skipped.
+    Skipped,
+    By1,
+    By2,
+    By4,
+    By8,
+    By16,
+}
+
+impl From<u8> for Oversampling {
+    /// Convert from the value BSEC reports, which is the register value of the sensor.
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Oversampling::By1,
+            2 => Oversampling::By2,
+            3 => Oversampling::By4,
+            4 => Oversampling::By8,
+            5 => Oversampling::By16,
+            _ => Oversampling::Skipped,
+        }
+    }
+}
+
+impl From<Oversampling> for u8 {
+    fn from(value: Oversampling) -> Self {
+        match value {
+            Oversampling::Skipped => 0,
+            Oversampling::By1 => 1,
+            Oversampling::By2 => 2,
+            Oversampling::By4 => 3,
+            Oversampling::By8 => 4,
+            Oversampling::By16 => 5,
+        }
+    }
+}
+
+/// A compensated measurement of the sensor.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Measurement {
+    /// Temperature, in °C.
+    pub temperature: f32,
+    /// Relative humidity, in %.
+    pub humidity: f32,
+    /// Pressure, in Pa, if it was measured.
+    pub pressure: Option<f32>,
+    /// Gas resistance, in Ohm, if a valid gas measurement was performed.
+    pub gas_resistance: Option<f32>,
+}
+
+/// A sensor, providing the measurements for BSEC.
+///
+/// This decouples the [`BsecDriver`](crate::BsecDriver) from a specific sensor driver. An
+/// implementation for the `drogue-bme680` crate is provided by
+/// [`Bme680ControllerSensor`](crate::Bme680ControllerSensor), others (like the `bme680` crate, or
+/// Linux `i2cdev`) can be added by implementing this trait.
+pub trait EnvironmentalSensor {
+    type Error: Debug;
+
+    /// Apply the settings BSEC requested: the oversampling and the heater.
+    fn configure(&mut self, control: &Control) -> Result<(), Self::Error>;
+
+    /// Trigger a measurement, using the applied settings, and wait for its result.
+    ///
+    /// Returns `None` if the sensor didn't provide any data.
+    fn measure(&mut self) -> Result<Option<Measurement>, Self::Error>;
+}
+
+/// The async counterpart of [`EnvironmentalSensor`], used by the
+/// [`AsyncBsecDriver`](crate::AsyncBsecDriver).
+///
+/// An implementation for a BME680, using `embedded-hal-async`, is provided by
+/// [`AsyncBme680`](crate::AsyncBme680).
+#[cfg(feature = "async")]
+#[allow(async_fn_in_trait)]
+pub trait AsyncEnvironmentalSensor {
+    type Error: Debug;
+
+    /// Apply the settings BSEC requested: the oversampling and the heater.
+    async fn configure(&mut self, control: &Control) -> Result<(), Self::Error>;
+
+    /// Trigger a measurement, using the applied settings, and wait for its result.
+    ///
+    /// Returns `None` if the sensor didn't provide any data.
+    async fn measure(&mut self) -> Result<Option<Measurement>, Self::Error>;
+}
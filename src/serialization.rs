@@ -0,0 +1,90 @@
+//! Serialization of the public types, using `serde`.
+//!
+//! Most types derive their implementations, the ones which need a custom representation are
+//! implemented here:
+//!
+//! * [`Accuracy`] is a struct of the `value` (`None` when unreliable) and the `accuracy`, from
+//!   0 (unreliable) to 3 (high).
+//! * [`Warning`] is the return code of the BSEC library.
+//! * `Milliseconds` is the number of milliseconds, see [`milliseconds`].
+
+use crate::ffi::bsec_library_return_t;
+use crate::{Accuracy, Warning};
+use core::convert::TryFrom;
+use serde::de::{Error, Unexpected};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+impl<T: Serialize> Serialize for Accuracy<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Accuracy", 2)?;
+        s.serialize_field("value", &self.value())?;
+        s.serialize_field("accuracy", &self.accuracy())?;
+        s.end()
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Accuracy<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Accuracy")]
+        struct Fields<T> {
+            #[serde(default = "Option::default")]
+            value: Option<T>,
+            accuracy: u8,
+        }
+
+        let fields = Fields::<T>::deserialize(deserializer)?;
+        match (fields.accuracy, fields.value) {
+            (0, _) => Ok(Accuracy::Unreliable),
+            (1, Some(value)) => Ok(Accuracy::Low(value)),
+            (2, Some(value)) => Ok(Accuracy::Medium(value)),
+            (3, Some(value)) => Ok(Accuracy::High(value)),
+            (1..=3, None) => Err(D::Error::missing_field("value")),
+            (accuracy, _) => Err(D::Error::invalid_value(
+                Unexpected::Unsigned(accuracy as u64),
+                &"an accuracy from 0 to 3",
+            )),
+        }
+    }
+}
+
+impl Serialize for Warning {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.0 as i32)
+    }
+}
+
+impl<'de> Deserialize<'de> for Warning {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = i32::deserialize(deserializer)?;
+        bsec_library_return_t::try_from(code)
+            .map(Warning)
+            .map_err(|_| {
+                D::Error::invalid_value(
+                    Unexpected::Signed(code as i64),
+                    &"a return code of the BSEC library",
+                )
+            })
+    }
+}
+
+/// (De)serialize `Milliseconds` as the number of milliseconds, for use with
+/// `#[serde(with = "...")]`.
+pub(crate) mod milliseconds {
+    use embedded_time::duration::Milliseconds;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Milliseconds,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(value.0)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Milliseconds, D::Error> {
+        u32::deserialize(deserializer).map(Milliseconds)
+    }
+}
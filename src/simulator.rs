@@ -0,0 +1,681 @@
+//! A simulated BME680, for testing on the host.
+//!
+//! [`Bme680Simulator`] emulates the register map of the sensor on the I2C bus: the chip ID, the
+//! calibration parameters, the control registers, the heater and the field data. Measurements
+//! are taken from a [`Scenario`], at the time of a [`SimulatedClock`], so that a whole pipeline
+//! (I2C, sensor driver, [`BsecDriver`](crate::BsecDriver)) can run without a board:
+//!
+//! ```
+//! # #[cfg(all(feature = "drogue-bme680", not(feature = "bsec2")))]
+//! # fn main() {
+//! use drogue_bme680::{Bme680Controller, Bme680Sensor, Configuration, StaticProvider};
+//! use drogue_bsec::simulator::*;
+//! use drogue_bsec::{bsec_virtual_sensor_t, Bme680ControllerSensor, Bsec, BsecDriver, SampleRate};
+//!
+//! let clock = SimulatedClock::new();
+//! let i2c = Bme680Simulator::new(&clock, Address::Secondary, Scenario::Cooking);
+//! let bme680 = Bme680Sensor::from(i2c, drogue_bme680::Address::Secondary).unwrap();
+//! let controller = Bme680Controller::new(
+//!     bme680,
+//!     SimulatedDelay::new(&clock),
+//!     Configuration::standard(),
+//!     StaticProvider(25),
+//! )
+//! .unwrap();
+//!
+//! let mut bsec = Bsec::new().unwrap().unwrap();
+//! bsec.update_subscription(SampleRate::LowPower, bsec_virtual_sensor_t::ALL)
+//!     .unwrap();
+//! let mut driver = BsecDriver::new(bsec, Bme680ControllerSensor::new(controller), &clock);
+//!
+//! // an hour of measurements
+//! while clock.elapsed().0 < 3_600_000 {
+//!     if let Some(outputs) = driver.poll().unwrap().outputs {
+//!         println!("IAQ: {:?}", outputs.iaq);
+//!     }
+//!     driver.sleep().unwrap();
+//! }
+//! # }
+//! # #[cfg(not(all(feature = "drogue-bme680", not(feature = "bsec2"))))]
+//! # fn main() {}
+//! ```
+
+use crate::bme680::*;
+use std::boxed::Box;
+use std::cell::Cell;
+use std::f32::consts::PI;
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+use embedded_time::duration::Milliseconds;
+use embedded_time::fraction::Fraction;
+use embedded_time::{clock, Clock, Instant};
+
+pub use crate::bme680::Address;
+
+/// The heater temperature, in °C, the gas resistance of the scenarios is valid for.
+const NOMINAL_HEATER_TEMPERATURE: f32 = 320.0;
+/// The minimum heating duration, in milliseconds, to reach a stable heater temperature.
+const MIN_HEATING_DURATION: u32 = 20;
+
+/// A clock, which only advances when told to, e.g. by a [`SimulatedDelay`].
+#[derive(Debug, Default)]
+pub struct SimulatedClock {
+    nanos: Cell<u64>,
+}
+
+impl SimulatedClock {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The time since the start of the simulation.
+    pub fn elapsed(&self) -> Milliseconds<u64> {
+        Milliseconds(self.nanos.get() / 1_000_000)
+    }
+
+    pub fn advance(&self, duration: Milliseconds<u64>) {
+        self.advance_nanos(duration.0 * 1_000_000);
+    }
+
+    fn advance_nanos(&self, nanos: u64) {
+        self.nanos.set(self.nanos.get() + nanos);
+    }
+
+    fn seconds(&self) -> f32 {
+        (self.nanos.get() as f64 / 1_000_000_000.0) as f32
+    }
+}
+
+impl Clock for SimulatedClock {
+    type T = u64;
+    const SCALING_FACTOR: Fraction = Fraction::new(1, 1_000_000_000);
+
+    fn try_now(&self) -> Result<Instant<Self>, clock::Error> {
+        Ok(Instant::new(self.nanos.get()))
+    }
+}
+
+/// A delay, advancing a [`SimulatedClock`] instead of waiting.
+#[derive(Copy, Clone, Debug)]
+pub struct SimulatedDelay<'c> {
+    clock: &'c SimulatedClock,
+}
+
+impl<'c> SimulatedDelay<'c> {
+    pub fn new(clock: &'c SimulatedClock) -> Self {
+        SimulatedDelay { clock }
+    }
+}
+
+impl DelayMs<u8> for SimulatedDelay<'_> {
+    fn delay_ms(&mut self, ms: u8) {
+        self.clock.advance(Milliseconds(ms as u64));
+    }
+}
+
+impl DelayMs<u16> for SimulatedDelay<'_> {
+    fn delay_ms(&mut self, ms: u16) {
+        self.clock.advance(Milliseconds(ms as u64));
+    }
+}
+
+impl DelayMs<u32> for SimulatedDelay<'_> {
+    fn delay_ms(&mut self, ms: u32) {
+        self.clock.advance(Milliseconds(ms as u64));
+    }
+}
+
+impl DelayMs<Milliseconds> for SimulatedDelay<'_> {
+    fn delay_ms(&mut self, ms: Milliseconds) {
+        self.clock.advance(Milliseconds(ms.0 as u64));
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for SimulatedDelay<'_> {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.clock.advance_nanos(ns as u64);
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        self.clock.advance(Milliseconds(ms as u64));
+    }
+}
+
+/// The environmental conditions at the sensor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Conditions {
+    /// Temperature, in °C.
+    pub temperature: f32,
+    /// Relative humidity, in %.
+    pub humidity: f32,
+    /// Pressure, in Pa.
+    pub pressure: f32,
+    /// Gas resistance, in Ohm, with the heater at 320°C.
+    pub gas_resistance: f32,
+}
+
+/// The development of the [`Conditions`] over time.
+///
+/// The events of the scripted scenarios start after 10 minutes, giving BSEC some time to settle
+/// in clean air.
+pub enum Scenario {
+    /// Clean air, with slight variations over the day.
+    CleanAir,
+    /// Cooking for 30 minutes: warm and humid air, with a lot of VOCs.
+    Cooking,
+    /// Opening the window for 20 minutes: cold outside air, with less VOCs than inside.
+    OpenWindow,
+    /// The conditions, provided by a function of the time in seconds.
+    Custom(Box<dyn Fn(f32) -> Conditions>),
+}
+
+impl Scenario {
+    /// The conditions, at a time in seconds since the start of the simulation.
+    pub fn conditions(&self, time: f32) -> Conditions {
+        match self {
+            Scenario::CleanAir => clean_air(time),
+            Scenario::Cooking => {
+                let cooking = exposure(time, 600.0, 2400.0, 300.0, 900.0);
+                let air = clean_air(time);
+                Conditions {
+                    temperature: air.temperature + 4.0 * cooking,
+                    humidity: air.humidity + 25.0 * cooking,
+                    gas_resistance: air.gas_resistance / (1.0 + 9.0 * cooking),
+                    ..air
+                }
+            }
+            Scenario::OpenWindow => {
+                let window = exposure(time, 600.0, 1800.0, 300.0, 900.0);
+                let air = clean_air(time);
+                Conditions {
+                    temperature: mix(air.temperature, 10.0, window),
+                    humidity: mix(air.humidity, 70.0, window),
+                    gas_resistance: mix(air.gas_resistance, 400_000.0, window),
+                    ..air
+                }
+            }
+            Scenario::Custom(conditions) => conditions(time),
+        }
+    }
+}
+
+fn clean_air(time: f32) -> Conditions {
+    let day = (2.0 * PI * time / 86_400.0).sin();
+    let drift = (2.0 * PI * time / 1_800.0).sin();
+    Conditions {
+        temperature: 21.0 + 1.0 * day,
+        humidity: 45.0 - 3.0 * day,
+        pressure: 101_325.0 + 50.0 * day,
+        gas_resistance: 200_000.0 + 10_000.0 * drift,
+    }
+}
+
+/// The exposure to an event from `start` to `end`, between 0 and 1, rising and decaying
+/// exponentially, with the time constants `rise` and `decay`.
+fn exposure(time: f32, start: f32, end: f32, rise: f32, decay: f32) -> f32 {
+    let during = |time: f32| 1.0 - (-(time - start) / rise).exp();
+    if time < start {
+        0.0
+    } else if time < end {
+        during(time)
+    } else {
+        during(end) * (-(time - end) / decay).exp()
+    }
+}
+
+fn mix(inside: f32, outside: f32, ratio: f32) -> f32 {
+    inside + (outside - inside) * ratio
+}
+
+/// An error of the simulated I2C bus.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SimulatorError {
+    /// The address doesn't match the address of the sensor.
+    AddressNack,
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::Error for SimulatorError {
+    fn kind(&self) -> embedded_hal_async::i2c::ErrorKind {
+        use embedded_hal_async::i2c::{ErrorKind, NoAcknowledgeSource};
+        match self {
+            SimulatorError::AddressNack => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+        }
+    }
+}
+
+/// A BME680 on a simulated I2C bus.
+///
+/// Writing to the registers works like with the actual sensor: a single byte selects the
+/// register to read from, otherwise the bytes are pairs of register and value. Triggering a
+/// forced mode measurement immediately provides the field data, for the conditions of the
+/// scenario at the current time of the clock.
+pub struct Bme680Simulator<'c> {
+    clock: &'c SimulatedClock,
+    address: u8,
+    scenario: Scenario,
+    calibration: Calibration,
+    registers: [u8; 256],
+    pointer: u8,
+    measurements: u32,
+}
+
+impl<'c> Bme680Simulator<'c> {
+    pub fn new(clock: &'c SimulatedClock, address: Address, scenario: Scenario) -> Self {
+        let mut simulator = Bme680Simulator {
+            clock,
+            address: address as u8,
+            scenario,
+            calibration: Calibration::TYPICAL,
+            registers: [0u8; 256],
+            pointer: 0,
+            measurements: 0,
+        };
+        simulator.reset();
+        simulator
+    }
+
+    pub fn set_scenario(&mut self, scenario: Scenario) {
+        self.scenario = scenario;
+    }
+
+    /// The number of measurements performed so far.
+    pub fn measurements(&self) -> u32 {
+        self.measurements
+    }
+
+    /// The current conditions of the scenario.
+    pub fn conditions(&self) -> Conditions {
+        self.scenario.conditions(self.clock.seconds())
+    }
+
+    /// The raw register map.
+    pub fn registers(&self) -> &[u8; 256] {
+        &self.registers
+    }
+
+    /// Reset the registers to the state after power on.
+    fn reset(&mut self) {
+        self.registers = [0u8; 256];
+        self.pointer = 0;
+
+        let (coeff_1, coeff_2) = self.calibration.coefficients();
+        copy_to(&mut self.registers, REG_COEFF_1, &coeff_1);
+        copy_to(&mut self.registers, REG_COEFF_2, &coeff_2);
+        self.registers[REG_RES_HEAT_RANGE as usize] = self.calibration.res_heat_range << 4;
+        self.registers[REG_RES_HEAT_VAL as usize] = self.calibration.res_heat_val as u8;
+        self.registers[REG_RANGE_SW_ERR as usize] = (self.calibration.range_sw_err << 4) as u8;
+        self.registers[REG_ID as usize] = CHIP_ID;
+    }
+
+    fn register(&self, register: u8) -> u8 {
+        self.registers[register as usize]
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) {
+        match register {
+            REG_RESET if value == SOFT_RESET => self.reset(),
+            REG_CTRL_MEAS => {
+                self.registers[register as usize] = value;
+                if value & MODE_MASK == MODE_FORCED {
+                    self.measure();
+                    // returns to sleep mode, once the measurement is done
+                    self.registers[register as usize] &= !MODE_MASK;
+                }
+            }
+            // the control registers, everything else is read only
+            REG_RES_HEAT_0..=REG_CONFIG => {
+                self.registers[register as usize] = value;
+            }
+            _ => {}
+        }
+    }
+
+    fn measure(&mut self) {
+        let conditions = self.conditions();
+        let calibration = self.calibration;
+
+        let ctrl_meas = self.register(REG_CTRL_MEAS);
+        let osrs_t = (ctrl_meas >> 5) & 0x07;
+        let osrs_p = (ctrl_meas >> 2) & 0x07;
+        let osrs_h = self.register(REG_CTRL_HUM) & 0x07;
+
+        let temperature = if osrs_t != 0 {
+            invert(0xF_FFFF, conditions.temperature, |adc| {
+                Calibration::temperature(calibration.t_fine(adc))
+            })
+        } else {
+            0x8_0000
+        };
+        let t_fine = calibration.t_fine(temperature);
+
+        let pressure = if osrs_p != 0 {
+            invert(0xF_FFFF, conditions.pressure, |adc| {
+                calibration.pressure(t_fine, adc)
+            })
+        } else {
+            0x8_0000
+        };
+
+        let humidity = if osrs_h != 0 {
+            invert(0xFFFF, conditions.humidity, |adc| {
+                calibration.humidity(t_fine, adc as u16)
+            }) as u16
+        } else {
+            0x8000
+        };
+
+        let run_gas = self.register(REG_CTRL_GAS_1) & RUN_GAS != 0
+            && self.register(REG_CTRL_GAS_0) & HEAT_OFF == 0;
+        let heater_temperature = self.heater_temperature(conditions.temperature);
+        let heat_stable = run_gas
+            && gas_wait_duration(self.register(REG_GAS_WAIT_0)) >= MIN_HEATING_DURATION
+            && heater_temperature > 0.0;
+
+        let (gas, gas_range) = if run_gas {
+            // a cooler heater results in a higher resistance
+            let resistance = conditions.gas_resistance * NOMINAL_HEATER_TEMPERATURE
+                / heater_temperature.max(NOMINAL_HEATER_TEMPERATURE / 16.0);
+            gas_adc(&calibration, resistance)
+        } else {
+            (0, 0)
+        };
+
+        let field = FieldData {
+            status: NEW_DATA,
+            pressure,
+            temperature,
+            humidity,
+            gas,
+            gas_range,
+            gas_valid: run_gas,
+            heat_stable,
+        };
+
+        let mut data = field.encode();
+        data[1] = self.measurements as u8;
+        copy_to(&mut self.registers, REG_FIELD_0, &data);
+
+        self.measurements += 1;
+    }
+
+    /// The heater temperature, set by the `res_heat_0` register.
+    fn heater_temperature(&self, ambient: f32) -> f32 {
+        let res_heat = self.register(REG_RES_HEAT_0);
+        if res_heat == 0 {
+            return 0.0;
+        }
+        invert(400, res_heat as f32, |temperature| {
+            self.calibration.res_heat(temperature as u16, ambient) as f32
+        }) as f32
+    }
+
+    fn check_address(&self, address: u8) -> Result<(), SimulatorError> {
+        if address == self.address {
+            Ok(())
+        } else {
+            Err(SimulatorError::AddressNack)
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        match bytes {
+            [register] => self.pointer = *register,
+            _ => {
+                for pair in bytes.chunks_exact(2) {
+                    self.write_register(pair[0], pair[1]);
+                }
+            }
+        }
+    }
+
+    fn read_bytes(&mut self, buffer: &mut [u8]) {
+        for b in buffer.iter_mut() {
+            *b = self.register(self.pointer);
+            self.pointer = self.pointer.wrapping_add(1);
+        }
+    }
+}
+
+impl Write for Bme680Simulator<'_> {
+    type Error = SimulatorError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check_address(address)?;
+        self.write_bytes(bytes);
+        Ok(())
+    }
+}
+
+impl Read for Bme680Simulator<'_> {
+    type Error = SimulatorError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.check_address(address)?;
+        self.read_bytes(buffer);
+        Ok(())
+    }
+}
+
+impl WriteRead for Bme680Simulator<'_> {
+    type Error = SimulatorError;
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.check_address(address)?;
+        self.write_bytes(bytes);
+        self.read_bytes(buffer);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::ErrorType for Bme680Simulator<'_> {
+    type Error = SimulatorError;
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::I2c for Bme680Simulator<'_> {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [embedded_hal_async::i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        use embedded_hal_async::i2c::Operation;
+
+        self.check_address(address)?;
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => self.write_bytes(bytes),
+                Operation::Read(buffer) => self.read_bytes(buffer),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn copy_to(registers: &mut [u8; 256], start: u8, data: &[u8]) {
+    let start = start as usize;
+    registers[start..start + data.len()].copy_from_slice(data);
+}
+
+/// Find the raw value in `0..=max`, which `compensate` maps closest to `target`.
+///
+/// The compensation must be monotonic, either increasing or decreasing.
+fn invert<F: Fn(u32) -> f32>(max: u32, target: f32, compensate: F) -> u32 {
+    let increasing = compensate(max) > compensate(0);
+    let (mut low, mut high) = (0, max);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if (compensate(mid) < target) == increasing {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    // `low` is the first value past the target, the one before may be closer
+    if low > 0 && (compensate(low - 1) - target).abs() <= (compensate(low) - target).abs() {
+        low - 1
+    } else {
+        low
+    }
+}
+
+/// The raw gas value and range, for a gas resistance.
+fn gas_adc(calibration: &Calibration, resistance: f32) -> (u16, u8) {
+    // with the raw value centered, the resistance of range 0 is ~8.5 MOhm, and halves per range
+    let range = (8_500_000.0 / resistance).log2().round().clamp(0.0, 15.0) as u8;
+    let adc = invert(0x3FF, resistance, |adc| {
+        calibration.gas_resistance(adc as u16, range)
+    });
+    (adc as u16, range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invert_increasing() {
+        let compensate = |adc: u32| adc as f32 * 0.5;
+        assert_eq!(invert(1000, 100.0, compensate), 200);
+        assert_eq!(invert(1000, 100.2, compensate), 200);
+        assert_eq!(invert(1000, 100.4, compensate), 201);
+        // out of range, clamped to the bounds
+        assert_eq!(invert(1000, -1.0, compensate), 0);
+        assert_eq!(invert(1000, 1000.0, compensate), 1000);
+    }
+
+    #[test]
+    fn invert_decreasing() {
+        let compensate = |adc: u32| 1000.0 - adc as f32 * 2.0;
+        assert_eq!(invert(400, 600.0, compensate), 200);
+        assert_eq!(invert(400, 599.5, compensate), 200);
+        assert_eq!(invert(400, 598.5, compensate), 201);
+        assert_eq!(invert(400, 2000.0, compensate), 0);
+        assert_eq!(invert(400, 0.0, compensate), 400);
+    }
+
+    #[test]
+    fn invert_compensation() {
+        let calibration = Calibration::TYPICAL;
+        for target in &[-20.0f32, 0.0, 21.5, 60.0] {
+            let adc = invert(0xF_FFFF, *target, |adc| {
+                Calibration::temperature(calibration.t_fine(adc))
+            });
+            let temperature = Calibration::temperature(calibration.t_fine(adc));
+            assert!((temperature - target).abs() < 0.01, "{}", temperature);
+        }
+    }
+
+    #[test]
+    fn gas_resistance_round_trip() {
+        let calibration = Calibration::TYPICAL;
+        for resistance in &[5_000.0f32, 50_000.0, 200_000.0, 1_000_000.0] {
+            let (adc, range) = gas_adc(&calibration, *resistance);
+            let measured = calibration.gas_resistance(adc, range);
+            assert!((measured / resistance - 1.0).abs() < 0.01, "{}", measured);
+        }
+    }
+
+    #[test]
+    fn registers_after_reset() {
+        let clock = SimulatedClock::new();
+        let mut simulator = Bme680Simulator::new(&clock, Address::Primary, Scenario::CleanAir);
+        assert_eq!(simulator.register(REG_ID), CHIP_ID);
+
+        let mut id = [0u8];
+        assert_eq!(
+            simulator.write_read(Address::Secondary as u8, &[REG_ID], &mut id),
+            Err(SimulatorError::AddressNack)
+        );
+        simulator
+            .write_read(Address::Primary as u8, &[REG_ID], &mut id)
+            .unwrap();
+        assert_eq!(id, [CHIP_ID]);
+
+        // read only registers are ignored, control registers are reset
+        simulator
+            .write(Address::Primary as u8, &[REG_ID, 0, REG_CTRL_HUM, 0x05])
+            .unwrap();
+        assert_eq!(simulator.register(REG_ID), CHIP_ID);
+        assert_eq!(simulator.register(REG_CTRL_HUM), 0x05);
+        simulator
+            .write(Address::Primary as u8, &[REG_RESET, SOFT_RESET])
+            .unwrap();
+        assert_eq!(simulator.register(REG_CTRL_HUM), 0);
+    }
+
+    #[test]
+    fn forced_measurement() {
+        let clock = SimulatedClock::new();
+        let mut simulator = Bme680Simulator::new(&clock, Address::Primary, Scenario::CleanAir);
+        let conditions = simulator.conditions();
+        let calibration = Calibration::TYPICAL;
+
+        let res_heat = calibration.res_heat(320, conditions.temperature);
+        simulator
+            .write(
+                Address::Primary as u8,
+                &[
+                    REG_CTRL_HUM,
+                    0b001,
+                    REG_RES_HEAT_0,
+                    res_heat,
+                    REG_GAS_WAIT_0,
+                    0x59,
+                    REG_CTRL_GAS_1,
+                    RUN_GAS,
+                    REG_CTRL_MEAS,
+                    (0b010 << 5) | (0b101 << 2) | MODE_FORCED,
+                ],
+            )
+            .unwrap();
+        assert_eq!(simulator.measurements(), 1);
+        assert_eq!(simulator.register(REG_CTRL_MEAS) & MODE_MASK, 0);
+
+        let mut field = [0u8; FIELD_LEN];
+        simulator
+            .write_read(Address::Primary as u8, &[REG_FIELD_0], &mut field)
+            .unwrap();
+        assert_eq!(field[0] & NEW_DATA, NEW_DATA);
+        assert_eq!(field[14] & (GAS_VALID | HEAT_STAB), GAS_VALID | HEAT_STAB);
+
+        let adc = |msb: usize| {
+            ((field[msb] as u32) << 12)
+                | ((field[msb + 1] as u32) << 4)
+                | (field[msb + 2] as u32 >> 4)
+        };
+        let t_fine = calibration.t_fine(adc(5));
+        let temperature = Calibration::temperature(t_fine);
+        let pressure = calibration.pressure(t_fine, adc(2));
+        let humidity = calibration.humidity(t_fine, ((field[8] as u16) << 8) | field[9] as u16);
+        let gas = ((field[13] as u16) << 2) | (field[14] as u16 >> 6);
+        let gas_resistance = calibration.gas_resistance(gas, field[14] & 0x0F);
+
+        assert!((temperature - conditions.temperature).abs() < 0.01);
+        assert!((pressure - conditions.pressure).abs() < 1.0);
+        assert!((humidity - conditions.humidity).abs() < 0.1);
+        assert!((gas_resistance / conditions.gas_resistance - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn clock_and_delay() {
+        let clock = SimulatedClock::new();
+        let mut delay = SimulatedDelay::new(&clock);
+        delay.delay_ms(1500u32);
+        delay.delay_ms(Milliseconds(500u32));
+        assert_eq!(clock.elapsed(), Milliseconds(2000u64));
+        assert_eq!(
+            crate::Timestamp::from_instant(&clock.try_now().unwrap()),
+            Some(crate::Timestamp::from_millis(2000))
+        );
+    }
+}
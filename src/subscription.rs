@@ -0,0 +1,201 @@
+use crate::ffi::*;
+use crate::{Error, Result, SampleRate, BSEC_SAMPLE_RATE_DISABLED};
+use core::convert::TryInto;
+use core::fmt::{Display, Formatter};
+
+/// Virtual sensors which are derived from the gas sensor, and must share the same sample rate.
+const GAS_SENSORS: &[bsec_virtual_sensor_t] = &[
+    bsec_virtual_sensor_t::BSEC_OUTPUT_IAQ,
+    bsec_virtual_sensor_t::BSEC_OUTPUT_STATIC_IAQ,
+    bsec_virtual_sensor_t::BSEC_OUTPUT_CO2_EQUIVALENT,
+    bsec_virtual_sensor_t::BSEC_OUTPUT_BREATH_VOC_EQUIVALENT,
+    bsec_virtual_sensor_t::BSEC_OUTPUT_RAW_GAS,
+    bsec_virtual_sensor_t::BSEC_OUTPUT_STABILIZATION_STATUS,
+    bsec_virtual_sensor_t::BSEC_OUTPUT_RUN_IN_STATUS,
+    #[cfg(not(feature = "bsec2"))]
+    bsec_virtual_sensor_t::BSEC_OUTPUT_COMPENSATED_GAS,
+    bsec_virtual_sensor_t::BSEC_OUTPUT_GAS_PERCENTAGE,
+];
+
+/// A subscription to virtual sensors, each with its own sample rate.
+///
+/// All sensors start out disabled:
+///
+/// ```ignore
+/// let subscription = Subscription::new()
+///     .with(bsec_virtual_sensor_t::BSEC_OUTPUT_IAQ, SampleRate::LowPower)
+///     .with(bsec_virtual_sensor_t::BSEC_OUTPUT_RAW_TEMPERATURE, SampleRate::UltraLowPower);
+/// bsec.subscribe(&subscription)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Subscription {
+    pub(crate) sensors: [bsec_sensor_configuration_t; NUM_VIRTUAL_SENSORS as usize],
+}
+
+impl Default for Subscription {
+    fn default() -> Self {
+        let mut sensors = [bsec_sensor_configuration_t {
+            sensor_id: 0,
+            sample_rate: BSEC_SAMPLE_RATE_DISABLED,
+        }; NUM_VIRTUAL_SENSORS as usize];
+        for (configuration, sensor) in sensors.iter_mut().zip(bsec_virtual_sensor_t::ALL) {
+            configuration.sensor_id = *sensor as u8;
+        }
+        Subscription { sensors }
+    }
+}
+
+impl Subscription {
+    /// Create a new subscription, with all sensors disabled.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the sample rate of a sensor.
+    pub fn with(mut self, sensor: bsec_virtual_sensor_t, sample_rate: SampleRate) -> Self {
+        self.set(sensor, sample_rate);
+        self
+    }
+
+    /// Set the sample rate of multiple sensors.
+    pub fn with_all(mut self, sensors: &[bsec_virtual_sensor_t], sample_rate: SampleRate) -> Self {
+        for sensor in sensors {
+            self.set(*sensor, sample_rate);
+        }
+        self
+    }
+
+    /// Set the sample rate of a sensor.
+    pub fn set(&mut self, sensor: bsec_virtual_sensor_t, sample_rate: SampleRate) {
+        for s in &mut self.sensors {
+            if s.sensor_id == sensor as u8 {
+                s.sample_rate = sample_rate.frequency();
+            }
+        }
+    }
+
+    /// Get the sample rate of a sensor.
+    pub fn sample_rate(&self, sensor: bsec_virtual_sensor_t) -> SampleRate {
+        self.sensors
+            .iter()
+            .find(|s| s.sensor_id == sensor as u8)
+            .and_then(|s| SampleRate::from_frequency(s.sample_rate))
+            .unwrap_or(SampleRate::Disabled)
+    }
+
+    /// Check if the combination of sample rates is one that BSEC accepts.
+    ///
+    /// All sensors derived from the gas sensor must share the same sample rate. Sensors
+    /// requesting an [`SampleRate::OnDemand`] measurement are not considered.
+    pub fn validate(&self) -> Result<()> {
+        let mut gas_rate = None;
+
+        for sensor in GAS_SENSORS {
+            match (self.sample_rate(*sensor), gas_rate) {
+                (SampleRate::Disabled, _) | (SampleRate::OnDemand, _) => {}
+                (rate, None) => gas_rate = Some(rate),
+                (rate, Some(gas_rate)) if rate == gas_rate => {}
+                (_, Some(_)) => {
+                    return Err(Error::UpdateSubscription(
+                        bsec_library_return_t::BSEC_E_SU_MULTGASSAMPLINTVL,
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Subscription {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mut first = true;
+        for sensor in bsec_virtual_sensor_t::ALL {
+            match self.sample_rate(*sensor) {
+                SampleRate::Disabled => {}
+                rate => {
+                    if !first {
+                        f.write_str(", ")?;
+                    }
+                    first = false;
+                    write!(f, "{:?}: {:?}", sensor, rate)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The physical inputs required by a subscription.
+///
+/// Each field holds the sample rate (in Hz) at which BSEC expects the input, or `None` if the
+/// input is not required.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RequiredInputs {
+    pub temperature: Option<f32>,
+    pub humidity: Option<f32>,
+    pub pressure: Option<f32>,
+    pub gas_resistance: Option<f32>,
+    pub heat_source: Option<f32>,
+}
+
+impl RequiredInputs {
+    pub(crate) fn from_sensors(sensors: &[bsec_sensor_configuration_t]) -> Self {
+        let mut result = RequiredInputs::default();
+
+        for sensor in sensors {
+            if sensor.sample_rate == BSEC_SAMPLE_RATE_DISABLED {
+                continue;
+            }
+            let rate = Some(sensor.sample_rate);
+            match sensor.sensor_id.try_into() {
+                Ok(bsec_physical_sensor_t::BSEC_INPUT_TEMPERATURE) => result.temperature = rate,
+                Ok(bsec_physical_sensor_t::BSEC_INPUT_HUMIDITY) => result.humidity = rate,
+                Ok(bsec_physical_sensor_t::BSEC_INPUT_PRESSURE) => result.pressure = rate,
+                Ok(bsec_physical_sensor_t::BSEC_INPUT_GASRESISTOR) => {
+                    result.gas_resistance = rate
+                }
+                Ok(bsec_physical_sensor_t::BSEC_INPUT_HEATSOURCE) => result.heat_source = rate,
+                _ => {
+                    // Unknown input, we ignore it
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Check if no input is required at all.
+    pub fn is_empty(&self) -> bool {
+        *self == Default::default()
+    }
+}
+
+impl Display for RequiredInputs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let inputs = [
+            ("temperature", self.temperature),
+            ("humidity", self.humidity),
+            ("pressure", self.pressure),
+            ("gas resistance", self.gas_resistance),
+            ("heat source", self.heat_source),
+        ];
+
+        let mut first = true;
+        for (name, rate) in inputs.iter() {
+            if let Some(rate) = rate {
+                if !first {
+                    f.write_str(", ")?;
+                }
+                first = false;
+                write!(f, "{} @ {} Hz", name, rate)?;
+            }
+        }
+
+        if first {
+            f.write_str("none")?;
+        }
+
+        Ok(())
+    }
+}
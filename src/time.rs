@@ -0,0 +1,81 @@
+use core::convert::TryFrom;
+use embedded_time::duration::{Generic, Milliseconds, Nanoseconds};
+use embedded_time::{Clock, Instant};
+
+/// A point in time, in nanoseconds.
+///
+/// This is the representation used by the BSEC library itself. Unlike a `u32` based
+/// [`Milliseconds`] value, it doesn't wrap after ~49.7 days. With the `serde` feature, it is
+/// serialized as the number of nanoseconds.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Timestamp(pub i64);
+
+impl Timestamp {
+    pub const fn from_nanos(nanos: i64) -> Self {
+        Timestamp(nanos)
+    }
+
+    pub const fn from_millis(millis: i64) -> Self {
+        Timestamp(millis * 1_000_000)
+    }
+
+    pub const fn as_nanos(&self) -> i64 {
+        self.0
+    }
+
+    pub const fn as_millis(&self) -> i64 {
+        self.0 / 1_000_000
+    }
+
+    /// Convert an instant of an `embedded_time` clock.
+    ///
+    /// Returns `None` if the instant cannot be represented in nanoseconds.
+    pub fn from_instant<C>(instant: &Instant<C>) -> Option<Self>
+    where
+        C: Clock,
+        Nanoseconds<u64>: TryFrom<Generic<C::T>>,
+    {
+        Nanoseconds::<u64>::try_from(instant.duration_since_epoch())
+            .ok()
+            .map(Into::into)
+    }
+
+    /// The duration since an earlier timestamp, or `None` if `earlier` is actually later, or the
+    /// difference overflows.
+    pub fn checked_duration_since(&self, earlier: Timestamp) -> Option<Nanoseconds<u64>> {
+        match self.0.checked_sub(earlier.0) {
+            Some(diff) if diff >= 0 => Some(Nanoseconds(diff as u64)),
+            _ => None,
+        }
+    }
+}
+
+impl From<Milliseconds<u32>> for Timestamp {
+    fn from(value: Milliseconds<u32>) -> Self {
+        Timestamp::from_millis(value.0 as i64)
+    }
+}
+
+impl From<Milliseconds<u64>> for Timestamp {
+    /// Convert a duration since the epoch, saturating at the largest timestamp (~292 years).
+    fn from(value: Milliseconds<u64>) -> Self {
+        Timestamp(
+            i64::try_from(value.0)
+                .ok()
+                .and_then(|millis| millis.checked_mul(1_000_000))
+                .unwrap_or(i64::MAX),
+        )
+    }
+}
+
+impl From<Nanoseconds<u64>> for Timestamp {
+    /// Convert a duration since the epoch, saturating at the largest timestamp (~292 years).
+    fn from(value: Nanoseconds<u64>) -> Self {
+        Timestamp(i64::try_from(value.0).unwrap_or(i64::MAX))
+    }
+}
@@ -0,0 +1,547 @@
+//! Trace recording of the calls to the BSEC library.
+//!
+//! With the `dump` feature, a [`Bsec`](crate::Bsec) instance can record every call to
+//! `bsec_set_configuration`, `bsec_set_state`, `bsec_update_subscription`,
+//! `bsec_sensor_control` and `bsec_do_steps` into a [`TraceSink`], so that problems in the field
+//! can be reproduced offline. With the `std`
+//! feature, a recorded trace can be read back using [`Trace::parse`], and replayed using the
+//! [`replay`](crate::replay) module.
+//!
+//! The trace is a binary format, all values are little endian. It starts with a header:
+//!
+//! | Field           | Size | Content                                       |
+//! |-----------------|------|-----------------------------------------------|
+//! | Magic           | 4    | `BTRC`                                        |
+//! | Format version  | 1    | [`TRACE_VERSION`]                             |
+//! | Flags           | 1    | Bit 0: BSEC 2.x, bit 1: fresh instance        |
+//! | Library version | 4    | Major, minor, major bugfix and minor bugfix   |
+//!
+//! Followed by records, each consisting of the kind (`u8`), the length of the payload (`u16`) and
+//! the payload:
+//!
+//! * [`RECORD_UPDATE_SUBSCRIPTION`]: the return code (`i16`), the number of requested sensors
+//!   (`u8`), followed by the sensor ID (`u8`) and sample rate (`f32`) of each, and the same for
+//!   the required sensors.
+//! * [`RECORD_SENSOR_CONTROL`]: the timestamp of the call (`i64`, ns), the return code (`i16`),
+//!   and the settings: next call (`i64`, ns), process data (`u32`), heater temperature (`u16`),
+//!   heating duration (`u16`), run gas (`u8`), pressure, temperature and humidity oversampling
+//!   (`u8` each) and trigger measurement (`u8`). With BSEC 2.x, followed by the operation mode
+//!   (`u8`), the length of the heater profile (`u8`), and its 10 temperatures and 10 durations
+//!   (`u16` each).
+//! * [`RECORD_DO_STEPS`]: the return code (`i16`), the number of inputs (`u8`), followed by the
+//!   sensor ID (`u8`), signal (`f32`) and timestamp (`i64`, ns) of each, and the number of
+//!   outputs (`u8`), followed by the sensor ID (`u8`), signal (`f32`), accuracy (`u8`) and
+//!   timestamp (`i64`, ns) of each.
+//! * [`RECORD_SET_CONFIGURATION`]: the return code (`i16`), followed by the configuration.
+//! * [`RECORD_SET_STATE`]: the return code (`i16`), followed by the state.
+//!
+//! Readers must skip records of unknown kinds, using the length.
+
+#[cfg(feature = "dump")]
+use crate::ffi::*;
+#[cfg(feature = "std")]
+use crate::Timestamp;
+use crate::Version;
+#[cfg(feature = "dump")]
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use core::fmt::{Display, Formatter};
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+/// The magic bytes, starting a trace.
+pub const TRACE_MAGIC: [u8; 4] = *b"BTRC";
+/// The version of the trace format.
+pub const TRACE_VERSION: u8 = 1;
+/// Flag of the header, set if the trace was recorded using BSEC 2.x.
+pub const FLAG_BSEC2: u8 = 0x01;
+/// Flag of the header, set if the recording started before the first call to the instance, so
+/// the trace holds all calls which changed its state.
+pub const FLAG_FRESH_INSTANCE: u8 = 0x02;
+
+/// A call to `bsec_update_subscription`.
+pub const RECORD_UPDATE_SUBSCRIPTION: u8 = 1;
+/// A call to `bsec_sensor_control`.
+pub const RECORD_SENSOR_CONTROL: u8 = 2;
+/// A call to `bsec_do_steps`.
+pub const RECORD_DO_STEPS: u8 = 3;
+/// A call to `bsec_set_configuration`.
+pub const RECORD_SET_CONFIGURATION: u8 = 4;
+/// A call to `bsec_set_state`.
+pub const RECORD_SET_STATE: u8 = 5;
+
+/// The largest payload, which is a call to `bsec_do_steps` with all inputs and outputs. The
+/// configuration and the state aren't buffered, but written directly.
+#[cfg(feature = "dump")]
+const MAX_PAYLOAD_LEN: usize =
+    2 + 1 + NUM_PHYSICAL_SENSORS as usize * 13 + 1 + NUM_VIRTUAL_SENSORS as usize * 14;
+
+/// Receives the recorded trace, e.g. to send it over RTT or a UART, or write it to a file.
+///
+/// The data is provided in order. A call provides the header, a record, or the part of a record
+/// before or after the configuration or state, which is provided as is.
+pub trait TraceSink {
+    fn write(&mut self, data: &[u8]);
+}
+
+impl<F> TraceSink for F
+where
+    F: FnMut(&[u8]),
+{
+    fn write(&mut self, data: &[u8]) {
+        self(data)
+    }
+}
+
+/// A sink, writing the trace to a [`std::io::Write`], like a file.
+#[cfg(feature = "std")]
+pub struct IoSink<W: std::io::Write>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> TraceSink for IoSink<W> {
+    fn write(&mut self, data: &[u8]) {
+        if let Err(err) = self.0.write_all(data) {
+            log::warn!("Failed to write trace: {}", err);
+        }
+    }
+}
+
+/// Records the calls to the BSEC library into a sink.
+#[cfg(feature = "dump")]
+pub(crate) struct Tracer {
+    sink: &'static mut (dyn TraceSink + Send),
+}
+
+#[cfg(feature = "dump")]
+impl Tracer {
+    /// Create a new tracer, writing the header of the trace.
+    ///
+    /// The instance is fresh, if there were no calls to it yet.
+    pub fn new(sink: &'static mut (dyn TraceSink + Send), version: Version, fresh: bool) -> Self {
+        let mut flags = 0;
+        if cfg!(feature = "bsec2") {
+            flags |= FLAG_BSEC2;
+        }
+        if fresh {
+            flags |= FLAG_FRESH_INSTANCE;
+        }
+
+        let mut header = [0u8; 10];
+        header[..4].copy_from_slice(&TRACE_MAGIC);
+        header[4] = TRACE_VERSION;
+        header[5] = flags;
+        header[6] = version.major;
+        header[7] = version.minor;
+        header[8] = version.major_bugfix;
+        header[9] = version.minor_bugfix;
+        sink.write(&header);
+
+        Tracer { sink }
+    }
+
+    pub fn into_sink(self) -> &'static mut (dyn TraceSink + Send) {
+        self.sink
+    }
+
+    pub fn set_configuration(&mut self, result: bsec_library_return_t, configuration: &[u8]) {
+        self.blob(RECORD_SET_CONFIGURATION, result, configuration);
+    }
+
+    pub fn set_state(&mut self, result: bsec_library_return_t, state: &[u8]) {
+        self.blob(RECORD_SET_STATE, result, state);
+    }
+
+    /// Write a record of the return code and a blob, which is too large to be buffered.
+    fn blob(&mut self, kind: u8, result: bsec_library_return_t, blob: &[u8]) {
+        let payload_len = match u16::try_from(2 + blob.len()) {
+            Ok(len) => len,
+            Err(_) => {
+                log::warn!("Skipping trace record {}, of {} bytes", kind, blob.len());
+                return;
+            }
+        };
+
+        let mut header = [0u8; 5];
+        header[0] = kind;
+        header[1..3].copy_from_slice(&payload_len.to_le_bytes());
+        header[3..].copy_from_slice(&(result as i16).to_le_bytes());
+        self.sink.write(&header);
+        self.sink.write(blob);
+    }
+
+    pub fn update_subscription(
+        &mut self,
+        result: bsec_library_return_t,
+        requested: &[bsec_sensor_configuration_t],
+        required: &[bsec_sensor_configuration_t],
+    ) {
+        let mut record = RecordWriter::new(RECORD_UPDATE_SUBSCRIPTION);
+        record.put_i16(result as i16);
+        for sensors in &[requested, required] {
+            record.put_u8(sensors.len() as u8);
+            for sensor in sensors.iter() {
+                record.put_u8(sensor.sensor_id);
+                record.put_f32(sensor.sample_rate);
+            }
+        }
+        record.write_to(self.sink);
+    }
+
+    pub fn sensor_control(
+        &mut self,
+        now: i64,
+        result: bsec_library_return_t,
+        settings: &bsec_bme_settings_t,
+    ) {
+        let mut record = RecordWriter::new(RECORD_SENSOR_CONTROL);
+        record.put_i64(now);
+        record.put_i16(result as i16);
+        record.put_i64(settings.next_call);
+        record.put_u32(settings.process_data);
+        record.put_u16(settings.heater_temperature);
+        #[cfg(not(feature = "bsec2"))]
+        record.put_u16(settings.heating_duration);
+        #[cfg(feature = "bsec2")]
+        record.put_u16(settings.heater_duration);
+        record.put_u8(settings.run_gas);
+        record.put_u8(settings.pressure_oversampling);
+        record.put_u8(settings.temperature_oversampling);
+        record.put_u8(settings.humidity_oversampling);
+        record.put_u8(settings.trigger_measurement);
+        #[cfg(feature = "bsec2")]
+        {
+            record.put_u8(settings.op_mode);
+            record.put_u8(settings.heater_profile_len);
+            for temperature in &settings.heater_temperature_profile {
+                record.put_u16(*temperature);
+            }
+            for duration in &settings.heater_duration_profile {
+                record.put_u16(*duration);
+            }
+        }
+        record.write_to(self.sink);
+    }
+
+    pub fn do_steps(
+        &mut self,
+        result: bsec_library_return_t,
+        inputs: &[bsec_input_t],
+        outputs: &[bsec_output_t],
+    ) {
+        let mut record = RecordWriter::new(RECORD_DO_STEPS);
+        record.put_i16(result as i16);
+        record.put_u8(inputs.len() as u8);
+        for input in inputs {
+            record.put_u8(input.sensor_id);
+            record.put_f32(input.signal);
+            record.put_i64(input.time_stamp);
+        }
+        record.put_u8(outputs.len() as u8);
+        for output in outputs {
+            record.put_u8(output.sensor_id);
+            record.put_f32(output.signal);
+            record.put_u8(output.accuracy);
+            record.put_i64(output.time_stamp);
+        }
+        record.write_to(self.sink);
+    }
+}
+
+/// A record, which is being encoded.
+#[cfg(feature = "dump")]
+struct RecordWriter {
+    buf: [u8; 3 + MAX_PAYLOAD_LEN],
+    len: usize,
+}
+
+#[cfg(feature = "dump")]
+impl RecordWriter {
+    fn new(kind: u8) -> Self {
+        let mut buf = [0u8; 3 + MAX_PAYLOAD_LEN];
+        buf[0] = kind;
+        RecordWriter { buf, len: 3 }
+    }
+
+    fn put(&mut self, data: &[u8]) {
+        self.buf[self.len..self.len + data.len()].copy_from_slice(data);
+        self.len += data.len();
+    }
+
+    fn put_u8(&mut self, value: u8) {
+        self.put(&[value]);
+    }
+
+    fn put_u16(&mut self, value: u16) {
+        self.put(&value.to_le_bytes());
+    }
+
+    fn put_i16(&mut self, value: i16) {
+        self.put(&value.to_le_bytes());
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.put(&value.to_le_bytes());
+    }
+
+    fn put_i64(&mut self, value: i64) {
+        self.put(&value.to_le_bytes());
+    }
+
+    fn put_f32(&mut self, value: f32) {
+        self.put(&value.to_le_bytes());
+    }
+
+    fn write_to(mut self, sink: &mut dyn TraceSink) {
+        let payload_len = (self.len - 3) as u16;
+        self.buf[1..3].copy_from_slice(&payload_len.to_le_bytes());
+        sink.write(&self.buf[..self.len]);
+    }
+}
+
+/// A trace, read back from its binary format.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct Trace {
+    /// The version of the library, which recorded the trace.
+    pub version: Version,
+    /// The flags of the header, like [`FLAG_BSEC2`].
+    pub flags: u8,
+    pub records: Vec<Record>,
+}
+
+/// A recorded call to the BSEC library.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+    UpdateSubscription {
+        result: i16,
+        requested: Vec<RecordedSensor>,
+        required: Vec<RecordedSensor>,
+    },
+    SensorControl {
+        timestamp: Timestamp,
+        result: i16,
+        settings: RecordedSettings,
+    },
+    DoSteps {
+        result: i16,
+        inputs: Vec<RecordedSignal>,
+        outputs: Vec<RecordedSignal>,
+    },
+    SetConfiguration {
+        result: i16,
+        configuration: Vec<u8>,
+    },
+    SetState {
+        result: i16,
+        state: Vec<u8>,
+    },
+}
+
+/// A sensor of a subscription, and its sample rate.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RecordedSensor {
+    pub sensor_id: u8,
+    pub sample_rate: f32,
+}
+
+/// The settings returned by `bsec_sensor_control`, common to all library versions.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RecordedSettings {
+    pub next_call: Timestamp,
+    pub process_data: u32,
+    pub heater_temperature: u16,
+    pub heating_duration: u16,
+    pub run_gas: bool,
+    pub pressure_oversampling: u8,
+    pub temperature_oversampling: u8,
+    pub humidity_oversampling: u8,
+    pub trigger_measurement: bool,
+}
+
+/// An input or output signal of `bsec_do_steps`.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RecordedSignal {
+    pub sensor_id: u8,
+    pub signal: f32,
+    /// The accuracy of an output, `0` for inputs.
+    pub accuracy: u8,
+    pub timestamp: Timestamp,
+}
+
+/// An error reading a trace.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TraceError {
+    /// The data doesn't start with [`TRACE_MAGIC`].
+    Magic,
+    /// The format version isn't supported.
+    Version(u8),
+    /// The data ends in the middle of the header or a record.
+    Truncated,
+}
+
+#[cfg(feature = "std")]
+impl Display for TraceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TraceError::Magic => write!(f, "Not a BSEC trace"),
+            TraceError::Version(version) => {
+                write!(f, "Unsupported trace format version: {}", version)
+            }
+            TraceError::Truncated => write!(f, "Trace is truncated"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TraceError {}
+
+#[cfg(feature = "std")]
+impl Trace {
+    /// Parse a trace, skipping records of unknown kinds.
+    pub fn parse(data: &[u8]) -> Result<Self, TraceError> {
+        let mut reader = Reader(data);
+
+        if reader.take(4)? != TRACE_MAGIC {
+            return Err(TraceError::Magic);
+        }
+        let version = reader.u8()?;
+        if version != TRACE_VERSION {
+            return Err(TraceError::Version(version));
+        }
+        let flags = reader.u8()?;
+        let version = Version {
+            major: reader.u8()?,
+            minor: reader.u8()?,
+            major_bugfix: reader.u8()?,
+            minor_bugfix: reader.u8()?,
+        };
+
+        let mut records = Vec::new();
+        while !reader.0.is_empty() {
+            let kind = reader.u8()?;
+            let len = reader.u16()? as usize;
+            let mut payload = Reader(reader.take(len)?);
+
+            let record = match kind {
+                RECORD_UPDATE_SUBSCRIPTION => Record::UpdateSubscription {
+                    result: payload.i16()?,
+                    requested: payload.sensors()?,
+                    required: payload.sensors()?,
+                },
+                RECORD_SENSOR_CONTROL => Record::SensorControl {
+                    timestamp: payload.timestamp()?,
+                    result: payload.i16()?,
+                    settings: RecordedSettings {
+                        next_call: payload.timestamp()?,
+                        process_data: payload.u32()?,
+                        heater_temperature: payload.u16()?,
+                        heating_duration: payload.u16()?,
+                        run_gas: payload.u8()? != 0,
+                        pressure_oversampling: payload.u8()?,
+                        temperature_oversampling: payload.u8()?,
+                        humidity_oversampling: payload.u8()?,
+                        trigger_measurement: payload.u8()? != 0,
+                    },
+                },
+                RECORD_DO_STEPS => {
+                    let result = payload.i16()?;
+                    let inputs = payload.signals(false)?;
+                    let outputs = payload.signals(true)?;
+                    Record::DoSteps {
+                        result,
+                        inputs,
+                        outputs,
+                    }
+                }
+                RECORD_SET_CONFIGURATION => Record::SetConfiguration {
+                    result: payload.i16()?,
+                    configuration: payload.0.to_vec(),
+                },
+                RECORD_SET_STATE => Record::SetState {
+                    result: payload.i16()?,
+                    state: payload.0.to_vec(),
+                },
+                _ => continue,
+            };
+            records.push(record);
+        }
+
+        Ok(Trace {
+            version,
+            flags,
+            records,
+        })
+    }
+}
+
+/// Reads the values of a trace.
+#[cfg(feature = "std")]
+struct Reader<'a>(&'a [u8]);
+
+#[cfg(feature = "std")]
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], TraceError> {
+        if self.0.len() < len {
+            return Err(TraceError::Truncated);
+        }
+        let (data, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(data)
+    }
+
+    fn u8(&mut self) -> Result<u8, TraceError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, TraceError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn i16(&mut self) -> Result<i16, TraceError> {
+        Ok(i16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, TraceError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, TraceError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn timestamp(&mut self) -> Result<Timestamp, TraceError> {
+        let nanos = i64::from_le_bytes(self.take(8)?.try_into().unwrap());
+        Ok(Timestamp::from_nanos(nanos))
+    }
+
+    fn sensors(&mut self) -> Result<Vec<RecordedSensor>, TraceError> {
+        (0..self.u8()?)
+            .map(|_| {
+                Ok(RecordedSensor {
+                    sensor_id: self.u8()?,
+                    sample_rate: self.f32()?,
+                })
+            })
+            .collect()
+    }
+
+    fn signals(&mut self, outputs: bool) -> Result<Vec<RecordedSignal>, TraceError> {
+        (0..self.u8()?)
+            .map(|_| {
+                Ok(RecordedSignal {
+                    sensor_id: self.u8()?,
+                    signal: self.f32()?,
+                    accuracy: if outputs { self.u8()? } else { 0 },
+                    timestamp: self.timestamp()?,
+                })
+            })
+            .collect()
+    }
+}
@@ -1,2 +1,2 @@
-#include "inc/bsec_datatypes.h"
-#include "inc/bsec_interface.h"
\ No newline at end of file
+#include "bsec_datatypes.h"
+#include "bsec_interface.h"
\ No newline at end of file
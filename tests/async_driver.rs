@@ -0,0 +1,135 @@
+//! Tests of the `AsyncBsecDriver`, running on the mock backend with a simulated sensor.
+
+#![cfg(all(feature = "mock", feature = "simulator", feature = "async"))]
+
+mod common;
+
+use common::*;
+use drogue_bsec::simulator::*;
+use drogue_bsec::*;
+use std::future::Future;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// Run a future to completion.
+///
+/// The simulated bus and delay complete immediately, so the future must never be pending, and
+/// there is no need for a real executor.
+fn block_on<F: Future>(future: F) -> F::Output {
+    fn raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut context = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    match future.as_mut().poll(&mut context) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("The simulation must not wait for anything"),
+    }
+}
+
+#[test]
+fn measures_the_simulated_conditions() {
+    let _guard = lock();
+    let clock = SimulatedClock::new();
+    let i2c = Bme680Simulator::new(
+        &clock,
+        Address::Secondary,
+        Scenario::Custom(Box::new(|_| CONDITIONS)),
+    );
+    let delay = SimulatedDelay::new(&clock);
+
+    block_on(async {
+        let sensor = AsyncBme680::new(i2c, Address::Secondary, delay)
+            .await
+            .unwrap();
+        let mut driver = AsyncBsecDriver::new(subscribed_bsec(), sensor, &clock);
+
+        let mut outputs = None;
+        for _ in 0..300 {
+            outputs = Some(driver.next_outputs().await.unwrap());
+        }
+        let outputs = outputs.unwrap();
+
+        // low power mode measures every 3 seconds
+        let elapsed = clock.elapsed().0;
+        assert!((897_000..=900_000).contains(&elapsed), "{}", elapsed);
+        assert!(driver.next_call() > driver.now().unwrap());
+
+        let measurement = driver.last_measurement().unwrap();
+        assert!((measurement.temperature - CONDITIONS.temperature).abs() < 0.5);
+        assert!((measurement.humidity - CONDITIONS.humidity).abs() < 1.0);
+        let pressure = measurement.pressure.unwrap();
+        assert!((pressure - CONDITIONS.pressure).abs() < 100.0);
+        let gas_resistance = measurement.gas_resistance.unwrap();
+        assert!((gas_resistance / CONDITIONS.gas_resistance - 1.0).abs() < 0.1);
+
+        assert_eq!(outputs.raw_temperature, Some(measurement.temperature));
+        assert_eq!(outputs.raw_gas_resistance, Some(gas_resistance));
+        assert!(matches!(outputs.iaq, Some(Accuracy::High(_))));
+    });
+}
+
+#[test]
+fn follows_the_scenario() {
+    let _guard = lock();
+    let clock = SimulatedClock::new();
+    let i2c = Bme680Simulator::new(&clock, Address::Secondary, Scenario::Cooking);
+    let delay = SimulatedDelay::new(&clock);
+
+    block_on(async {
+        let sensor = AsyncBme680::new(i2c, Address::Secondary, delay)
+            .await
+            .unwrap();
+        let mut driver = AsyncBsecDriver::new(subscribed_bsec(), sensor, &clock);
+
+        // clean air for the first 10 minutes, cooking for the next 30
+        let mut before = None;
+        let mut processed = 0;
+        while clock.elapsed().0 < 1_800_000 {
+            driver.next_outputs().await.unwrap();
+            processed += 1;
+            if before.is_none() && clock.elapsed().0 >= 590_000 {
+                before = driver.last_measurement().copied();
+            }
+        }
+        let before = before.unwrap();
+        let during = driver.last_measurement().unwrap();
+
+        assert!(during.temperature > before.temperature + 2.0);
+        assert!(during.humidity > before.humidity + 10.0);
+        assert!(during.gas_resistance.unwrap() < before.gas_resistance.unwrap() / 3.0);
+
+        let (_bsec, sensor) = driver.into_inner();
+        let (simulator, _delay) = sensor.release();
+        assert_eq!(simulator.measurements(), processed);
+    });
+}
+
+#[test]
+fn sleep_rounds_up() {
+    use embedded_hal_async::delay::DelayNs;
+
+    let _guard = lock();
+    let clock = SimulatedClock::new();
+    let i2c = Bme680Simulator::new(&clock, Address::Secondary, Scenario::CleanAir);
+    let mut delay = SimulatedDelay::new(&clock);
+
+    block_on(async {
+        let sensor = AsyncBme680::new(i2c, Address::Secondary, SimulatedDelay::new(&clock))
+            .await
+            .unwrap();
+        let mut driver = AsyncBsecDriver::new(subscribed_bsec(), sensor, &clock);
+        driver.next_outputs().await.unwrap();
+
+        // shift the clock off whole milliseconds, rounding down would wake up too early
+        delay.delay_ns(500_000).await;
+        driver.sleep().await.unwrap();
+        assert!(driver.now().unwrap() >= driver.next_call());
+    });
+}
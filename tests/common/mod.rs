@@ -0,0 +1,39 @@
+//! Fixtures shared by the tests, running on the mock backend.
+
+#![allow(dead_code)]
+
+#[cfg(feature = "simulator")]
+use drogue_bsec::simulator::Conditions;
+use drogue_bsec::*;
+use std::sync::{Mutex, MutexGuard};
+
+/// The mock keeps its state globally, like the real library, so the tests must not run in
+/// parallel.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Lock the mock for a test, even if a previous test failed while holding the lock.
+pub fn lock() -> MutexGuard<'static, ()> {
+    LOCK.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Create a new instance, the mock must be locked.
+pub fn bsec() -> Bsec {
+    Bsec::new().unwrap().expect("no other live instance")
+}
+
+/// Create a new instance, subscribed to all outputs in low power mode.
+pub fn subscribed_bsec() -> Bsec {
+    let mut bsec = bsec();
+    bsec.update_subscription(SampleRate::LowPower, bsec_virtual_sensor_t::ALL)
+        .unwrap();
+    bsec
+}
+
+/// Steady conditions, for the simulated sensor.
+#[cfg(feature = "simulator")]
+pub const CONDITIONS: Conditions = Conditions {
+    temperature: 23.0,
+    humidity: 40.0,
+    pressure: 98_000.0,
+    gas_resistance: 150_000.0,
+};
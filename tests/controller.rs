@@ -0,0 +1,65 @@
+//! End-to-end test of the blocking pipeline: the simulated BME680, the `Bme680Controller` of
+//! `drogue-bme680`, `Bme680ControllerSensor` and `BsecDriver`, running on the mock backend.
+
+#![cfg(all(feature = "mock", feature = "simulator", feature = "drogue-bme680"))]
+
+mod common;
+
+use common::CONDITIONS;
+use drogue_bme680::{Bme680Controller, Bme680Sensor, Configuration, StaticProvider};
+use drogue_bsec::simulator::*;
+use drogue_bsec::*;
+
+#[test]
+fn bsec_driver_over_the_simulator() {
+    let clock = SimulatedClock::new();
+    let i2c = Bme680Simulator::new(
+        &clock,
+        Address::Secondary,
+        Scenario::Custom(Box::new(|_| CONDITIONS)),
+    );
+    let bme680 = Bme680Sensor::from(i2c, drogue_bme680::Address::Secondary).unwrap();
+    let controller = Bme680Controller::new(
+        bme680,
+        SimulatedDelay::new(&clock),
+        Configuration::standard(),
+        StaticProvider(25),
+    )
+    .unwrap();
+
+    let mut bsec = Bsec::new().unwrap().unwrap();
+    bsec.update_subscription(SampleRate::LowPower, bsec_virtual_sensor_t::ALL)
+        .unwrap();
+    let mut driver = BsecDriver::new(bsec, Bme680ControllerSensor::new(controller), &clock);
+
+    let mut processed = 0;
+    let mut last = None;
+    while processed < 300 {
+        let poll = driver.poll().unwrap();
+        assert_eq!(poll.data.is_some(), poll.outputs.is_some());
+        if let (Some(data), Some(outputs)) = (poll.data, poll.outputs) {
+            processed += 1;
+            last = Some((data, outputs));
+        }
+        assert!(poll.next_call > driver.now().unwrap());
+        driver.sleep().unwrap();
+        assert!(driver.time_to_next_call().unwrap().is_none());
+    }
+
+    // low power mode measures every 3 seconds
+    let elapsed = clock.elapsed().0;
+    assert!((897_000..=900_000).contains(&elapsed), "{}", elapsed);
+
+    let (data, outputs) = last.unwrap();
+    assert!((data.temperature - CONDITIONS.temperature).abs() < 1.0);
+    assert!((data.humidity - CONDITIONS.humidity).abs() < 3.0);
+    let pressure = data.pressure.unwrap();
+    assert!((pressure - CONDITIONS.pressure).abs() < 200.0);
+    let gas_resistance = data.gas_resistance.unwrap();
+    assert!((gas_resistance / CONDITIONS.gas_resistance - 1.0).abs() < 0.2);
+
+    assert_eq!(outputs.raw_temperature, Some(data.temperature));
+    assert_eq!(outputs.raw_pressure, Some(pressure));
+    assert_eq!(outputs.raw_gas_resistance, Some(gas_resistance));
+    assert!(matches!(outputs.iaq, Some(Accuracy::High(_))));
+}
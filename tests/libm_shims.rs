@@ -0,0 +1,112 @@
+//! Check the bare metal builds of the BSEC library against the libm shims of `src/compat.rs`.
+//!
+//! This inspects the archives on disk, so it runs with any backend, including the mock. It uses
+//! `nm` (or whatever `NM` points to), and checks all Cortex-M variants found in the library
+//! directories, or in `BSEC_LIB_DIR`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+include!("../src/math_symbols.rs");
+
+/// The names of the shims, provided by `src/compat.rs`.
+fn provided_shims() -> Vec<&'static str> {
+    include_str!("../src/compat.rs")
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("pub extern \"C\" fn "))
+        .filter_map(|line| line.split('(').next())
+        .collect()
+}
+
+fn find_archives(dir: &Path, result: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.is_dir() {
+            find_archives(&path, result);
+        } else if path.file_name() == Some("libalgobsec.a".as_ref()) {
+            result.push(path);
+        }
+    }
+}
+
+/// The archives of the bare metal targets, which rely on the shims.
+fn bare_metal_archives() -> Vec<PathBuf> {
+    let project_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut dirs = vec![
+        project_dir.join("BSEC-Arduino-library").join("src"),
+        project_dir.join("Bosch-BSEC2-Library").join("src"),
+    ];
+    dirs.extend(env::var_os("BSEC_LIB_DIR").map(PathBuf::from));
+
+    let mut archives = Vec::new();
+    for dir in dirs {
+        find_archives(&dir, &mut archives);
+    }
+    archives.retain(|archive| archive.to_string_lossy().contains("cortex-m"));
+    archives.sort();
+    archives.dedup();
+    archives
+}
+
+fn undefined_symbols(archive: &Path) -> Vec<String> {
+    let nm = env::var("NM").unwrap_or_else(|_| "nm".into());
+    let output = Command::new(&nm)
+        .arg("-u")
+        .arg(archive)
+        .output()
+        .unwrap_or_else(|err| panic!("Unable to run '{}' (set NM to override): {}", nm, err));
+    assert!(
+        output.status.success(),
+        "'{}' failed on {}: {}",
+        nm,
+        archive.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn shims_parsed() {
+    let shims = provided_shims();
+    assert!(shims.contains(&"powf"));
+    for shim in shims {
+        assert!(MATH_SYMBOLS.contains(&shim), "Unknown shim: {}", shim);
+    }
+}
+
+#[test]
+fn all_referenced_math_functions_have_a_shim() {
+    let archives = bare_metal_archives();
+    if archives.is_empty() {
+        eprintln!("No bare metal build of the BSEC library found, nothing to check");
+        return;
+    }
+
+    let shims = provided_shims();
+    let mut missing = Vec::new();
+    for archive in &archives {
+        for symbol in undefined_symbols(archive) {
+            if MATH_SYMBOLS.contains(&symbol.as_str()) && !shims.contains(&symbol.as_str()) {
+                missing.push(format!("{} ({})", symbol, archive.display()));
+            }
+        }
+    }
+    missing.sort();
+    missing.dedup();
+
+    assert!(
+        missing.is_empty(),
+        "Math functions without a shim in src/compat.rs: {:#?}",
+        missing
+    );
+}
@@ -0,0 +1,257 @@
+//! Tests of the `Bsec` API, running on the mock backend.
+
+#![cfg(feature = "mock")]
+
+mod common;
+
+use common::*;
+use drogue_bsec::bsec_library_return_t::*;
+use drogue_bsec::bsec_virtual_sensor_t::*;
+use drogue_bsec::*;
+
+fn measurement(gas_resistance: f32) -> Measurement {
+    Measurement {
+        temperature: 21.5,
+        humidity: 45.0,
+        pressure: Some(101_300.0),
+        gas_resistance: Some(gas_resistance),
+    }
+}
+
+#[test]
+fn single_instance() {
+    let _guard = lock();
+    let bsec = bsec();
+    assert!(Bsec::new().unwrap().is_none());
+    drop(bsec);
+    assert!(Bsec::new().unwrap().is_some());
+}
+
+#[test]
+fn subscribe_reports_required_inputs() {
+    let _guard = lock();
+    let mut bsec = bsec();
+
+    let required = bsec
+        .set_subscription(SampleRate::LowPower, &[BSEC_OUTPUT_RAW_TEMPERATURE])
+        .unwrap();
+    assert!(required.temperature.is_some());
+    assert!(required.humidity.is_some());
+    assert!(required.pressure.is_some());
+    assert_eq!(required.gas_resistance, None);
+    assert_eq!(required.heat_source, None);
+
+    let required = bsec
+        .update_subscription(
+            SampleRate::LowPower,
+            &[
+                BSEC_OUTPUT_IAQ,
+                BSEC_OUTPUT_SENSOR_HEAT_COMPENSATED_TEMPERATURE,
+            ],
+        )
+        .unwrap();
+    assert_eq!(
+        required.gas_resistance,
+        Some(f32::from(SampleRate::LowPower))
+    );
+    assert!(required.heat_source.is_some());
+    assert_eq!(bsec.required_inputs(), &required);
+    assert_eq!(
+        bsec.subscription().sample_rate(BSEC_OUTPUT_RAW_TEMPERATURE),
+        SampleRate::LowPower
+    );
+
+    let required = bsec.set_subscription(SampleRate::Disabled, &[]).unwrap();
+    assert_eq!(required, RequiredInputs::default());
+}
+
+#[test]
+fn subscribe_rejects_mixed_gas_rates() {
+    let _guard = lock();
+    let mut bsec = bsec();
+
+    let subscription = Subscription::new()
+        .with(BSEC_OUTPUT_IAQ, SampleRate::LowPower)
+        .with(BSEC_OUTPUT_CO2_EQUIVALENT, SampleRate::UltraLowPower);
+    assert_eq!(
+        bsec.subscribe(&subscription).unwrap_err(),
+        Error::UpdateSubscription(BSEC_E_SU_MULTGASSAMPLINTVL)
+    );
+}
+
+#[test]
+fn control_and_process_loop() {
+    let _guard = lock();
+    let mut bsec = bsec();
+    bsec.set_subscription(
+        SampleRate::LowPower,
+        &[
+            BSEC_OUTPUT_IAQ,
+            BSEC_OUTPUT_RAW_TEMPERATURE,
+            BSEC_OUTPUT_RAW_GAS,
+        ],
+    )
+    .unwrap();
+
+    let mut now = Timestamp::from_millis(0);
+    let mut last = None;
+    for _ in 0..300 {
+        let control = bsec.sensor_control(now).unwrap();
+        assert!(control.warning.is_none());
+        assert!(control.trigger_measurement);
+        assert!(control.run_gas);
+        assert!(control.next_call > now);
+
+        let inputs = control.measurement_inputs(&measurement(50_000.0));
+        assert_eq!(inputs.temperature, Some(21.5));
+        assert_eq!(inputs.gas_resistance, Some(50_000.0));
+
+        let outputs = bsec.process_data(now, &inputs).unwrap();
+        assert_eq!(outputs.raw_temperature, Some(21.5));
+        assert_eq!(outputs.raw_gas_resistance, Some(50_000.0));
+        assert!(outputs.raw_humidity.is_none(), "not subscribed");
+
+        now = control.next_call;
+        last = Some(outputs);
+    }
+
+    // the accuracy rises, while the sensor is calibrating
+    match last.unwrap().iaq {
+        Some(Accuracy::High(iaq)) => assert_eq!(iaq, 25.0),
+        other => panic!("Unexpected IAQ: {:?}", other),
+    }
+}
+
+#[test]
+fn process_data_reports_missing_inputs() {
+    let _guard = lock();
+    let mut bsec = bsec();
+    bsec.set_subscription(SampleRate::LowPower, &[BSEC_OUTPUT_IAQ])
+        .unwrap();
+
+    let now = Timestamp::from_millis(0);
+    let control = bsec.sensor_control(now).unwrap();
+    let inputs = Inputs {
+        gas_resistance: None,
+        ..control.measurement_inputs(&measurement(50_000.0))
+    };
+    let outputs = bsec.process_data(now, &inputs).unwrap();
+    assert_eq!(outputs.missing_inputs, RequestedInputs::GAS);
+
+    // only the inputs requested by the last control are checked
+    let outputs = bsec
+        .process_data(Timestamp::from_millis(1), &Inputs::default())
+        .unwrap();
+    assert!(outputs.missing_inputs.is_empty());
+
+    let now = control.next_call;
+    let control = bsec.sensor_control(now).unwrap();
+    let inputs = control.measurement_inputs(&measurement(50_000.0));
+    let outputs = bsec.process_data(now, &inputs).unwrap();
+    assert!(outputs.missing_inputs.is_empty());
+}
+
+#[test]
+fn control_reports_timing_violation() {
+    let _guard = lock();
+    let mut bsec = bsec();
+    bsec.set_subscription(SampleRate::LowPower, &[BSEC_OUTPUT_RAW_TEMPERATURE])
+        .unwrap();
+
+    let control = bsec.sensor_control(Timestamp::from_millis(0)).unwrap();
+    let late = Timestamp::from_nanos(control.next_call.as_nanos() + 1_000_000_000);
+    let control = bsec.sensor_control(late).unwrap();
+    assert_eq!(
+        control.warning,
+        Some(Warning(BSEC_W_SC_CALL_TIMING_VIOLATION))
+    );
+    assert!(control.trigger_measurement);
+}
+
+#[test]
+fn process_data_rejects_invalid_gas() {
+    let _guard = lock();
+    let mut bsec = bsec();
+    bsec.set_subscription(SampleRate::LowPower, &[BSEC_OUTPUT_IAQ])
+        .unwrap();
+
+    let inputs = Inputs {
+        gas_resistance: Some(-1.0),
+        ..Default::default()
+    };
+    let err = bsec
+        .process_data(Timestamp::from_millis(0), &inputs)
+        .unwrap_err();
+    assert_eq!(err, Error::DoSteps(BSEC_E_DOSTEPS_VALUELIMITS));
+    assert_eq!(err.code(), BSEC_E_DOSTEPS_VALUELIMITS);
+    assert_eq!(
+        err.to_string(),
+        "do_steps: Value of input sensor signal is not in the valid range"
+    );
+}
+
+#[test]
+fn state_round_trip() {
+    let mut buffer = [0u8; MAX_STATE_BLOB_SIZE];
+
+    let state = {
+        let _guard = lock();
+        let mut bsec = bsec();
+        bsec.set_subscription(SampleRate::LowPower, &[BSEC_OUTPUT_IAQ])
+            .unwrap();
+        let mut now = Timestamp::from_millis(0);
+        for _ in 0..250 {
+            let control = bsec.sensor_control(now).unwrap();
+            bsec.process_data(now, &control.measurement_inputs(&measurement(80_000.0)))
+                .unwrap();
+            now = control.next_call;
+        }
+        bsec.get_state(&mut buffer).unwrap().to_vec()
+    };
+
+    let _guard = lock();
+
+    let mut bsec = bsec();
+    bsec.set_subscription(SampleRate::LowPower, &[BSEC_OUTPUT_IAQ])
+        .unwrap();
+    bsec.set_state(&state).unwrap();
+
+    // the restored baseline and calibration apply to the first measurement
+    let control = bsec.sensor_control(Timestamp::from_millis(0)).unwrap();
+    let outputs = bsec
+        .process_data(
+            Timestamp::from_millis(0),
+            &control.measurement_inputs(&measurement(40_000.0)),
+        )
+        .unwrap();
+    match outputs.iaq {
+        Some(Accuracy::High(iaq)) => assert_eq!(iaq, 100.0),
+        other => panic!("Unexpected IAQ: {:?}", other),
+    }
+}
+
+#[test]
+fn state_errors() {
+    let _guard = lock();
+    let mut bsec = bsec();
+
+    let mut small = [0u8; 4];
+    assert_eq!(
+        bsec.get_state(&mut small).unwrap_err(),
+        Error::GetState(BSEC_E_CONFIG_INSUFFICIENTBUFFER)
+    );
+
+    assert_eq!(
+        bsec.set_state(&[]).unwrap_err(),
+        Error::SetState(BSEC_E_CONFIG_EMPTY)
+    );
+
+    let mut buffer = [0u8; MAX_STATE_BLOB_SIZE];
+    let mut state = bsec.get_state(&mut buffer).unwrap().to_vec();
+    let last = state.len() - 1;
+    state[last] = state[last].wrapping_add(1);
+    assert_eq!(
+        bsec.set_state(&state).unwrap_err(),
+        Error::SetState(BSEC_E_CONFIG_CRCMISMATCH)
+    );
+}
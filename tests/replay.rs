@@ -0,0 +1,577 @@
+//! Tests of the `replay` module, and of reading back traces, running on the mock backend.
+
+#![cfg(all(feature = "mock", feature = "std"))]
+
+mod common;
+
+use common::*;
+use drogue_bsec::replay::*;
+#[cfg(feature = "dump")]
+use drogue_bsec::trace::*;
+use drogue_bsec::*;
+#[cfg(feature = "dump")]
+use std::sync::Mutex;
+
+/// Record a trace of `count` measurements, using all kinds of inputs.
+#[cfg(feature = "dump")]
+fn record(count: usize) -> (Vec<u8>, Vec<(Timestamp, Outputs)>) {
+    record_with(count, |_| {})
+}
+
+/// Record a trace of `count` measurements, after calling `setup` on the fresh instance.
+#[cfg(feature = "dump")]
+fn record_with<F: FnOnce(&mut Bsec)>(
+    count: usize,
+    setup: F,
+) -> (Vec<u8>, Vec<(Timestamp, Outputs)>) {
+    let data = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let sink = {
+        let data = data.clone();
+        Box::leak(Box::new(move |bytes: &[u8]| {
+            data.lock().unwrap().extend_from_slice(bytes)
+        }))
+    };
+
+    let mut bsec = bsec();
+    bsec.set_trace_sink(sink);
+    setup(&mut bsec);
+    bsec.set_subscription(SampleRate::LowPower, bsec_virtual_sensor_t::ALL)
+        .unwrap();
+
+    let mut now = Timestamp::from_millis(1_000);
+    let mut outputs = Vec::new();
+    for i in 0..count {
+        let control = bsec.sensor_control(now).unwrap();
+        let measurement = Measurement {
+            temperature: 21.0 + i as f32 * 0.01,
+            humidity: 45.0,
+            pressure: Some(99_000.0),
+            gas_resistance: Some(if i < count / 2 { 80_000.0 } else { 30_000.0 }),
+        };
+        let inputs = Inputs {
+            heat_source: Some(1.5),
+            disable_baseline_tracker: Some(i % 2 == 0),
+            ..control.measurement_inputs(&measurement)
+        };
+        outputs.push((now, bsec.process_data(now, &inputs).unwrap()));
+        now = control.next_call;
+    }
+    bsec.take_trace_sink();
+
+    let data = data.lock().unwrap().clone();
+    (data, outputs)
+}
+
+#[cfg(feature = "dump")]
+#[test]
+fn trace_round_trip() {
+    let _guard = lock();
+    let (data, outputs) = record(10);
+
+    let trace = Trace::parse(&data).unwrap();
+    assert_eq!(trace.version, version());
+    assert_eq!(trace.flags, FLAG_FRESH_INSTANCE);
+    assert_eq!(trace.records.len(), 1 + 2 * outputs.len());
+    assert!(matches!(
+        trace.records[0],
+        Record::UpdateSubscription { result: 0, .. }
+    ));
+
+    for (records, (timestamp, outputs)) in trace.records[1..].chunks(2).zip(&outputs) {
+        match &records[0] {
+            Record::SensorControl {
+                timestamp: recorded,
+                result,
+                settings,
+            } => {
+                assert_eq!(recorded, timestamp);
+                assert_eq!(*result, 0);
+                assert!(settings.trigger_measurement);
+                assert!(settings.run_gas);
+                assert!(settings.next_call > *timestamp);
+            }
+            other => panic!("Unexpected record: {:?}", other),
+        }
+        match &records[1] {
+            Record::DoSteps {
+                result,
+                inputs,
+                outputs: recorded,
+            } => {
+                assert_eq!(*result, 0);
+                assert_eq!(inputs.len(), 6);
+                assert!(inputs.iter().all(|input| input.timestamp == *timestamp));
+                let raw_gas = recorded
+                    .iter()
+                    .find(|output| {
+                        output.sensor_id == bsec_virtual_sensor_t::BSEC_OUTPUT_RAW_GAS as u8
+                    })
+                    .unwrap();
+                assert_eq!(Some(raw_gas.signal), outputs.raw_gas_resistance);
+                assert_eq!(raw_gas.timestamp, *timestamp);
+            }
+            other => panic!("Unexpected record: {:?}", other),
+        }
+    }
+}
+
+#[cfg(feature = "dump")]
+#[test]
+fn tracing_instance_is_send() {
+    fn send<T: Send>(_: T) {}
+
+    let _guard = lock();
+    let mut bsec = bsec();
+    bsec.set_trace_sink(Box::leak(Box::new(|_: &[u8]| {})));
+    send(bsec);
+}
+
+#[cfg(feature = "dump")]
+#[test]
+fn trace_skips_unknown_records() {
+    let _guard = lock();
+    let (data, _) = record(2);
+    let trace = Trace::parse(&data).unwrap();
+
+    // an unknown record right after the header, and one at the end
+    let unknown = [0x7f, 3, 0, 1, 2, 3];
+    let mut extended = data[..10].to_vec();
+    extended.extend_from_slice(&unknown);
+    extended.extend_from_slice(&data[10..]);
+    extended.extend_from_slice(&unknown);
+
+    let extended = Trace::parse(&extended).unwrap();
+    assert_eq!(extended.records, trace.records);
+}
+
+#[cfg(feature = "dump")]
+#[test]
+fn trace_errors() {
+    let _guard = lock();
+    let (data, _) = record(2);
+
+    assert_eq!(Trace::parse(b"ABCD").unwrap_err(), TraceError::Magic);
+    assert_eq!(Trace::parse(&data[..7]).unwrap_err(), TraceError::Truncated);
+    for len in &[data.len() - 1, data.len() - 30, 12] {
+        assert_eq!(
+            Trace::parse(&data[..*len]).unwrap_err(),
+            TraceError::Truncated,
+            "{} bytes",
+            len
+        );
+    }
+
+    let mut version = data.clone();
+    version[4] = TRACE_VERSION + 1;
+    assert_eq!(
+        Trace::parse(&version).unwrap_err(),
+        TraceError::Version(TRACE_VERSION + 1)
+    );
+
+    // a record which is too short for its kind
+    let mut short = data[..10].to_vec();
+    short.extend_from_slice(&[RECORD_DO_STEPS, 1, 0, 0]);
+    assert_eq!(Trace::parse(&short).unwrap_err(), TraceError::Truncated);
+}
+
+#[cfg(feature = "dump")]
+#[test]
+fn recording_keeps_all_inputs() {
+    let _guard = lock();
+    let (data, outputs) = record(4);
+    let recording = Recording::from_trace(&Trace::parse(&data).unwrap()).unwrap();
+
+    assert!(recording.fresh_instance);
+    assert!(matches!(recording.steps[0], Step::UpdateSubscription(_)));
+    assert_eq!(recording.steps.len(), 1 + 2 * outputs.len());
+    assert_eq!(recording.outputs.len(), outputs.len());
+
+    for (i, steps) in recording.steps[1..].chunks(2).enumerate() {
+        assert!(matches!(
+            steps[0],
+            Step::SensorControl { timestamp, .. } if timestamp == outputs[i].0
+        ));
+        match &steps[1] {
+            Step::DoSteps { timestamp, inputs } => {
+                assert_eq!(*timestamp, outputs[i].0);
+                assert_eq!(inputs.temperature, Some(21.0 + i as f32 * 0.01));
+                assert_eq!(inputs.humidity, Some(45.0));
+                assert_eq!(inputs.pressure, Some(99_000.0));
+                let gas_resistance = if i < 2 { 80_000.0 } else { 30_000.0 };
+                assert_eq!(inputs.gas_resistance, Some(gas_resistance));
+                assert_eq!(inputs.heat_source, Some(1.5));
+                assert_eq!(inputs.disable_baseline_tracker, Some(i % 2 == 0));
+            }
+            other => panic!("Unexpected step: {:?}", other),
+        }
+    }
+}
+
+#[cfg(feature = "dump")]
+#[test]
+fn recording_rejects_other_library() {
+    let _guard = lock();
+    let (data, _) = record(1);
+    let mut trace = Trace::parse(&data).unwrap();
+    trace.flags |= FLAG_BSEC2;
+
+    match Recording::from_trace(&trace) {
+        Err(ReplayError::Library { bsec2: true }) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[cfg(feature = "dump")]
+#[test]
+fn replay_matches_the_recording() {
+    let _guard = lock();
+    let (data, outputs) = record(250);
+    let recording = Recording::from_trace(&Trace::parse(&data).unwrap()).unwrap();
+
+    let mut bsec = bsec();
+    let mut comparator = Comparator::new(&recording.outputs, 0.0);
+    let mut timestamps = Vec::new();
+    let replayed = Replayer::new(&mut bsec)
+        .replay(&recording.steps, |timestamp, inputs, replayed| {
+            assert_eq!(inputs.heat_source, Some(1.5));
+            timestamps.push(timestamp);
+            comparator.compare(timestamp, replayed);
+            Ok(())
+        })
+        .unwrap();
+
+    assert_eq!(replayed.processed, outputs.len());
+    assert_eq!(replayed.diverged, 0);
+    assert!(timestamps.iter().eq(outputs.iter().map(|(t, _)| t)));
+
+    let comparison = comparator.finish();
+    assert_eq!(comparison.compared, outputs.len());
+    assert_eq!(comparison.unmatched, 0);
+    assert_eq!(comparison.mismatches, 0);
+    assert!(comparison.is_match(), "{}", comparison);
+}
+
+#[cfg(feature = "dump")]
+#[test]
+fn replay_detects_differences() {
+    let _guard = lock();
+    let (data, _) = record(20);
+    let mut recording = Recording::from_trace(&Trace::parse(&data).unwrap()).unwrap();
+
+    // a different output, and a different schedule
+    let raw_temperature = bsec_virtual_sensor_t::BSEC_OUTPUT_RAW_TEMPERATURE as u8;
+    for output in &mut recording.outputs[3].outputs {
+        if output.sensor_id == raw_temperature {
+            output.signal += 1.0;
+        }
+    }
+    if let Step::SensorControl { settings, .. } = &mut recording.steps[7] {
+        settings.next_call = Timestamp::from_nanos(settings.next_call.as_nanos() + 1);
+    }
+
+    let mut bsec = bsec();
+    let mut comparator = Comparator::new(&recording.outputs, 0.5);
+    let replayed = Replayer::new(&mut bsec)
+        .replay(&recording.steps, |timestamp, _, outputs| {
+            comparator.compare(timestamp, outputs);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(replayed.diverged, 1);
+
+    let comparison = comparator.finish();
+    assert_eq!(comparison.compared, 20);
+    assert_eq!(comparison.mismatches, 1);
+    assert!(!comparison.is_match());
+}
+
+#[cfg(feature = "dump")]
+#[test]
+fn replay_applies_configuration_and_state() {
+    let _guard = lock();
+    let state = {
+        let mut bsec = bsec();
+        bsec.set_subscription(SampleRate::LowPower, bsec_virtual_sensor_t::ALL)
+            .unwrap();
+        let mut now = Timestamp::from_millis(0);
+        for _ in 0..200 {
+            let control = bsec.sensor_control(now).unwrap();
+            let measurement = Measurement {
+                temperature: 21.5,
+                humidity: 45.0,
+                pressure: Some(99_000.0),
+                gas_resistance: Some(80_000.0),
+            };
+            bsec.process_data(now, &control.measurement_inputs(&measurement))
+                .unwrap();
+            now = control.next_call;
+        }
+        let mut buffer = [0u8; MAX_STATE_BLOB_SIZE];
+        bsec.get_state(&mut buffer).unwrap().to_vec()
+    };
+
+    let configuration = [1u8, 2, 3, 4, 5];
+    let (data, outputs) = record_with(4, |bsec| {
+        bsec.set_configuration(&configuration).unwrap();
+        bsec.set_state(&state).unwrap();
+    });
+
+    let trace = Trace::parse(&data).unwrap();
+    assert_eq!(
+        trace.records[..2],
+        [
+            Record::SetConfiguration {
+                result: 0,
+                configuration: configuration.to_vec(),
+            },
+            Record::SetState {
+                result: 0,
+                state: state.clone(),
+            },
+        ]
+    );
+
+    let recording = Recording::from_trace(&trace).unwrap();
+    assert!(matches!(
+        &recording.steps[..3],
+        [
+            Step::SetConfiguration(recorded_configuration),
+            Step::SetState(recorded_state),
+            Step::UpdateSubscription(_),
+        ] if recorded_configuration[..] == configuration && *recorded_state == state
+    ));
+
+    // the restored state is calibrated, which the replay must reproduce
+    assert!(matches!(outputs[0].1.iaq, Some(Accuracy::High(_))));
+    let mut bsec = bsec();
+    let mut comparator = Comparator::new(&recording.outputs, 0.0);
+    Replayer::new(&mut bsec)
+        .replay(&recording.steps, |timestamp, _, outputs| {
+            comparator.compare(timestamp, outputs);
+            Ok(())
+        })
+        .unwrap();
+    let comparison = comparator.finish();
+    assert!(comparison.is_match(), "{}", comparison);
+}
+
+#[cfg(feature = "dump")]
+#[test]
+fn recording_flags_used_instance() {
+    let _guard = lock();
+    let mut bsec = bsec();
+    bsec.set_subscription(SampleRate::LowPower, bsec_virtual_sensor_t::ALL)
+        .unwrap();
+
+    let data = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let sink = {
+        let data = data.clone();
+        Box::leak(Box::new(move |bytes: &[u8]| {
+            data.lock().unwrap().extend_from_slice(bytes)
+        }))
+    };
+    bsec.set_trace_sink(sink);
+    bsec.sensor_control(Timestamp::from_millis(0)).unwrap();
+    bsec.take_trace_sink();
+
+    let trace = Trace::parse(&data.lock().unwrap()).unwrap();
+    assert_eq!(trace.flags & FLAG_FRESH_INSTANCE, 0);
+    let recording = Recording::from_trace(&trace).unwrap();
+    assert!(!recording.fresh_instance);
+    assert_eq!(recording.steps.len(), 1);
+}
+
+#[test]
+fn comparison_requires_recorded_outputs() {
+    assert!(!Comparison::default().is_match());
+
+    let mut comparator = Comparator::new(&[], 0.0);
+    comparator.compare(Timestamp::from_millis(0), &Outputs::default());
+    let comparison = comparator.finish();
+    assert_eq!(comparison.compared, 0);
+    assert_eq!(comparison.unmatched, 1);
+    assert!(!comparison.is_match());
+}
+
+#[test]
+fn csv_samples() {
+    let csv = "timestamp_ms, temperature, humidity, pressure, gas_resistance\n\
+               0,21.5,45,101300,50000\n\
+               \n\
+               3000, 22, 46.5, ,\n\
+               6000,22.5,47,101200\n";
+    let samples = read_csv(csv.as_bytes()).unwrap();
+
+    assert_eq!(
+        samples,
+        vec![
+            Sample {
+                timestamp: Timestamp::from_millis(0),
+                measurement: Measurement {
+                    temperature: 21.5,
+                    humidity: 45.0,
+                    pressure: Some(101_300.0),
+                    gas_resistance: Some(50_000.0),
+                },
+            },
+            Sample {
+                timestamp: Timestamp::from_millis(3_000),
+                measurement: Measurement {
+                    temperature: 22.0,
+                    humidity: 46.5,
+                    pressure: None,
+                    gas_resistance: None,
+                },
+            },
+            Sample {
+                timestamp: Timestamp::from_millis(6_000),
+                measurement: Measurement {
+                    temperature: 22.5,
+                    humidity: 47.0,
+                    pressure: Some(101_200.0),
+                    gas_resistance: None,
+                },
+            },
+        ]
+    );
+
+    // without a header
+    assert_eq!(read_csv("0,21.5,45".as_bytes()).unwrap().len(), 1);
+}
+
+#[test]
+fn csv_errors() {
+    let error = |csv: &str| match read_csv(csv.as_bytes()) {
+        Err(ReplayError::Csv { line, message }) => (line, message),
+        other => panic!("Unexpected result: {:?}", other),
+    };
+
+    assert_eq!(
+        error("timestamp,temperature,humidity\n0,21.5\n"),
+        (
+            2,
+            "Expected at least timestamp, temperature and humidity".into()
+        )
+    );
+    assert_eq!(
+        error("0,21.5,45\nlater,21.5,45\n"),
+        (2, "Invalid timestamp".into())
+    );
+    assert_eq!(
+        error("0,21.5,45\n\n1000,warm,45\n"),
+        (3, "Invalid number".into())
+    );
+    assert_eq!(error("0,,45\n"), (1, "Missing value".into()));
+    assert_eq!(
+        error("9223372036854775807,21.5,45\n"),
+        (1, "Timestamp out of range".into())
+    );
+}
+
+#[test]
+fn run_follows_the_schedule() {
+    let _guard = lock();
+    let csv: String = (0..100)
+        .map(|i| format!("{},21.5,45,101300,50000\n", i * 1_000))
+        .collect();
+    let samples = read_csv(csv.as_bytes()).unwrap();
+
+    let mut bsec = bsec();
+    bsec.set_subscription(
+        SampleRate::LowPower,
+        &[bsec_virtual_sensor_t::BSEC_OUTPUT_RAW_TEMPERATURE],
+    )
+    .unwrap();
+    let mut timestamps = Vec::new();
+    let processed = Replayer::new(&mut bsec)
+        .run(&samples, |timestamp, inputs, outputs| {
+            assert_eq!(inputs.temperature, Some(21.5));
+            assert_eq!(outputs.raw_temperature, Some(21.5));
+            timestamps.push(timestamp.as_millis());
+            Ok(())
+        })
+        .unwrap();
+
+    // low power mode measures every 3 seconds, up to the last sample
+    assert_eq!(processed, 34);
+    assert_eq!(timestamps, (0..34).map(|i| i * 3_000).collect::<Vec<_>>());
+}
+
+fn outputs() -> Outputs {
+    Outputs {
+        iaq: Some(Accuracy::Medium(42.5)),
+        static_iaq: Some(Accuracy::Unreliable),
+        raw_temperature: Some(21.5),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn csv_output() {
+    let mut writer = OutputWriter::new(Vec::new(), Format::Csv);
+    writer
+        .write(Timestamp::from_millis(3_000), &outputs())
+        .unwrap();
+    writer
+        .write(Timestamp::from_millis(6_000), &Outputs::default())
+        .unwrap();
+    let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+    let lines: Vec<Vec<&str>> = csv.lines().map(|line| line.split(',').collect()).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[0][..6],
+        [
+            "timestamp_ms",
+            "iaq",
+            "iaq_accuracy",
+            "static_iaq",
+            "static_iaq_accuracy",
+            "co2_equivalent"
+        ]
+    );
+    assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+
+    let column = |row: usize, name: &str| {
+        let index = lines[0].iter().position(|n| *n == name).unwrap();
+        lines[row][index]
+    };
+    assert_eq!(column(1, "timestamp_ms"), "3000");
+    assert_eq!(column(1, "iaq"), "42.5");
+    assert_eq!(column(1, "iaq_accuracy"), "2");
+    assert_eq!(column(1, "static_iaq"), "");
+    assert_eq!(column(1, "static_iaq_accuracy"), "0");
+    assert_eq!(column(1, "raw_temperature"), "21.5");
+    assert_eq!(column(1, "raw_pressure"), "");
+    assert_eq!(column(2, "timestamp_ms"), "6000");
+    assert_eq!(column(2, "iaq_accuracy"), "");
+}
+
+#[test]
+fn json_output() {
+    let writer = OutputWriter::new(Vec::new(), Format::Json);
+    assert_eq!(writer.finish().unwrap(), b"[]\n");
+
+    let mut writer = OutputWriter::new(Vec::new(), Format::Json);
+    writer
+        .write(Timestamp::from_millis(3_000), &outputs())
+        .unwrap();
+    writer
+        .write(Timestamp::from_millis(6_000), &Outputs::default())
+        .unwrap();
+    let json = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+    let objects: Vec<&str> = json
+        .strip_prefix("[\n")
+        .and_then(|json| json.strip_suffix("\n]\n"))
+        .unwrap()
+        .split(",\n")
+        .collect();
+    assert_eq!(objects.len(), 2);
+    assert!(objects[0].starts_with(
+        r#"{"timestamp_ms":3000,"iaq":42.5,"iaq_accuracy":2,"static_iaq":null,"static_iaq_accuracy":0,"#
+    ));
+    assert!(objects[0].contains(r#","raw_temperature":21.5,"raw_pressure":null,"#));
+    assert!(objects[1].starts_with(r#"{"timestamp_ms":6000,"iaq":null,"iaq_accuracy":null,"#));
+    assert!(objects.iter().all(|object| object.ends_with('}')));
+}
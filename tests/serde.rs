@@ -0,0 +1,190 @@
+//! Tests of the serialization of the public types, using JSON.
+
+#![cfg(feature = "serde")]
+
+use drogue_bsec::bsec_library_return_t::*;
+use drogue_bsec::*;
+use serde_json::{from_str, json, to_string, to_value};
+
+#[cfg(feature = "mock")]
+mod common;
+
+#[test]
+fn accuracy_shape() {
+    assert_eq!(
+        to_string(&Accuracy::High(42.5f32)).unwrap(),
+        r#"{"value":42.5,"accuracy":3}"#
+    );
+    assert_eq!(
+        to_string(&Accuracy::Medium(1.0f32)).unwrap(),
+        r#"{"value":1.0,"accuracy":2}"#
+    );
+    assert_eq!(
+        to_string(&Accuracy::Low(0.5f32)).unwrap(),
+        r#"{"value":0.5,"accuracy":1}"#
+    );
+    assert_eq!(
+        to_string(&Accuracy::<f32>::Unreliable).unwrap(),
+        r#"{"value":null,"accuracy":0}"#
+    );
+}
+
+#[test]
+fn accuracy_round_trip() {
+    let values = [
+        Accuracy::Unreliable,
+        Accuracy::Low(0.5f32),
+        Accuracy::Medium(1.0),
+        Accuracy::High(42.5),
+    ];
+    for value in &values {
+        let json = to_string(value).unwrap();
+        let parsed: Accuracy<f32> = from_str(&json).unwrap();
+        assert_eq!(parsed.accuracy(), value.accuracy(), "{}", json);
+        assert_eq!(parsed.value(), value.value(), "{}", json);
+    }
+
+    // the value is optional when unreliable, and ignored
+    let parsed: Accuracy<f32> = from_str(r#"{"accuracy":0}"#).unwrap();
+    assert!(matches!(parsed, Accuracy::Unreliable));
+    let parsed: Accuracy<f32> = from_str(r#"{"value":1.0,"accuracy":0}"#).unwrap();
+    assert!(matches!(parsed, Accuracy::Unreliable));
+}
+
+#[test]
+fn accuracy_errors() {
+    let error = from_str::<Accuracy<f32>>(r#"{"accuracy":2}"#).unwrap_err();
+    assert!(
+        error.to_string().contains("missing field `value`"),
+        "{}",
+        error
+    );
+    let error = from_str::<Accuracy<f32>>(r#"{"value":1.0,"accuracy":4}"#).unwrap_err();
+    assert!(
+        error.to_string().contains("accuracy from 0 to 3"),
+        "{}",
+        error
+    );
+    assert!(from_str::<Accuracy<f32>>(r#"{"value":1.0}"#).is_err());
+}
+
+#[test]
+fn warning_is_the_return_code() {
+    let warning = Warning(BSEC_W_SC_CALL_TIMING_VIOLATION);
+    assert_eq!(to_string(&warning).unwrap(), "100");
+    assert_eq!(from_str::<Warning>("100").unwrap(), warning);
+    assert!(from_str::<Warning>("12345").is_err());
+}
+
+#[test]
+fn timestamp_is_nanoseconds() {
+    assert_eq!(to_string(&Timestamp::from_millis(3)).unwrap(), "3000000");
+    assert_eq!(
+        from_str::<Timestamp>("3000000").unwrap(),
+        Timestamp::from_millis(3)
+    );
+}
+
+#[test]
+fn measurement_round_trip() {
+    let measurement = Measurement {
+        temperature: 21.5,
+        humidity: 45.0,
+        pressure: Some(101_300.0),
+        gas_resistance: None,
+    };
+    let json = to_value(measurement).unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "temperature": 21.5,
+            "humidity": 45.0,
+            "pressure": 101_300.0,
+            "gas_resistance": null,
+        })
+    );
+    assert_eq!(
+        serde_json::from_value::<Measurement>(json).unwrap(),
+        measurement
+    );
+}
+
+#[cfg(feature = "mock")]
+mod mock {
+    use super::common::*;
+    use super::*;
+
+    fn control_and_outputs() -> (Control, Inputs, Outputs) {
+        let _guard = lock();
+        let mut bsec = subscribed_bsec();
+
+        let now = Timestamp::from_millis(0);
+        let control = bsec.sensor_control(now).unwrap();
+        let inputs = control.measurement_inputs(&Measurement {
+            temperature: 21.5,
+            humidity: 45.0,
+            pressure: Some(101_300.0),
+            gas_resistance: Some(50_000.0),
+        });
+        let outputs = bsec.process_data(now, &inputs).unwrap();
+        (control, inputs, outputs)
+    }
+
+    #[test]
+    fn control_round_trip() {
+        let (mut control, _, _) = control_and_outputs();
+        control.warning = Some(Warning(BSEC_W_SC_CALL_TIMING_VIOLATION));
+
+        let json = to_value(&control).unwrap();
+        assert_eq!(json["next_call"], json!(control.next_call.as_nanos()));
+        assert_eq!(json["heating_duration"], json!(control.heating_duration.0));
+        assert_eq!(json["process_data"], json!(control.process_data.bits()));
+        assert_eq!(json["warning"], json!(100));
+
+        let parsed: Control = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(to_value(&parsed).unwrap(), json);
+    }
+
+    #[test]
+    fn inputs_round_trip() {
+        let (_, inputs, _) = control_and_outputs();
+
+        let json = to_value(&inputs).unwrap();
+        assert_eq!(json["temperature"], json!(21.5));
+        assert_eq!(json["heat_source"], json!(null));
+
+        let parsed: Inputs = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(to_value(&parsed).unwrap(), json);
+    }
+
+    #[test]
+    fn outputs_round_trip() {
+        let (_, _, mut outputs) = control_and_outputs();
+        outputs.iaq = Some(Accuracy::Medium(42.5));
+        outputs.static_iaq = Some(Accuracy::Unreliable);
+
+        let json = to_value(&outputs).unwrap();
+        assert_eq!(json["iaq"], json!({"value": 42.5, "accuracy": 2}));
+        assert_eq!(json["static_iaq"], json!({"value": null, "accuracy": 0}));
+        assert_eq!(json["raw_temperature"], json!(21.5));
+
+        let parsed: Outputs = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(to_value(&parsed).unwrap(), json);
+    }
+
+    #[test]
+    fn version_round_trip() {
+        let version = version();
+        let json = to_value(version).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "major": version.major,
+                "minor": version.minor,
+                "major_bugfix": version.major_bugfix,
+                "minor_bugfix": version.minor_bugfix,
+            })
+        );
+        assert_eq!(serde_json::from_value::<Version>(json).unwrap(), version);
+    }
+}